@@ -1,6 +1,9 @@
 from transformers import CLIPProcessor, CLIPModel
 from PIL import Image
 import torch
+import io
+import json
+import hashlib
 import logging
 
 logging.basicConfig(level=logging.INFO)
@@ -18,33 +21,58 @@ TAXONOMY = {
     "Arte_Graficos": "Digital art, meme, vector graphic, screenshot of software",
 }
 
+# Used when a request brings no taxonomy of its own. The version changes whenever a category or
+# prompt changes, so clients can tell stale cached results apart
+TAXONOMY_VERSION = hashlib.sha256(json.dumps(TAXONOMY, sort_keys=True).encode()).hexdigest()[:16]
+
 class CLIPClassifier:
     def __init__(self, model_name="openai/clip-vit-base-patch32"):
+        self.model_name = model_name
         self.device = "cuda" if torch.cuda.is_available() else "cpu"
         logger.info(f"Loading CLIP model on {self.device}...")
         self.model = CLIPModel.from_pretrained(model_name).to(self.device)
         self.processor = CLIPProcessor.from_pretrained(model_name)
+        # Hub commit of the loaded weights, when known
+        self.model_version = getattr(self.model.config, "_commit_hash", None) or "unknown"
         self.categories = list(TAXONOMY.keys())
         self.prompts = list(TAXONOMY.values())
         logger.info("CLIP model loaded.")
 
-    def classify_batch(self, image_paths):
+    def classify_batch(self, image_paths, taxonomy=None, taxonomy_version=None):
+        """Classifies images read from the local filesystem; `path` in each result is the input path."""
+        sources = ((path, lambda p=path: Image.open(p)) for path in image_paths)
+        return self.classify_images(sources, taxonomy, taxonomy_version)
+
+    def classify_uploads(self, uploads, taxonomy=None, taxonomy_version=None):
+        """Classifies uploaded (name, bytes) pairs; `path` in each result is the upload's name."""
+        sources = ((name, lambda d=data: Image.open(io.BytesIO(d))) for name, data in uploads)
+        return self.classify_images(sources, taxonomy, taxonomy_version)
+
+    def classify_images(self, sources, taxonomy=None, taxonomy_version=None):
+        """`taxonomy` is a list of (name, prompt) pairs supplied by the client; without one the
+        built-in TAXONOMY is used. Every result echoes the version of the taxonomy applied."""
+        if taxonomy:
+            categories = [name for name, _ in taxonomy]
+            prompts = [prompt for _, prompt in taxonomy]
+        else:
+            categories, prompts, taxonomy_version = self.categories, self.prompts, TAXONOMY_VERSION
+
         images = []
-        valid_paths = []
-        for path in image_paths:
+        valid_keys = []
+        for key, open_image in sources:
             try:
-                img = Image.open(path).convert("RGB")
+                img = open_image().convert("RGB")
                 # Resize to max 512px to save VRAM and bus bandwidth
                 img.thumbnail((512, 512), Image.Resampling.LANCZOS)
                 images.append(img)
-                valid_paths.append(path)
+                valid_keys.append(key)
             except Exception as e:
-                logger.error(f"Error loading image {path}: {e}")
+                logger.error(f"Error loading image {key}: {e}")
                 
         if not images:
             return []
 
-        inputs = self.processor(text=self.prompts, images=images, return_tensors="pt", padding=True, truncation=True).to(self.device)
+        inputs = self.processor(text=prompts, images=images, return_tensors="pt", padding=True, truncation=True).to(self.device)
         
         with torch.no_grad():
             outputs = self.model(**inputs)
@@ -52,13 +80,18 @@ class CLIPClassifier:
             probs = logits_per_image.softmax(dim=1)
 
         results = []
-        for i, path in enumerate(valid_paths):
+        for i, key in enumerate(valid_keys):
             path_probs = probs[i].cpu().tolist()
             max_idx = path_probs.index(max(path_probs))
             results.append({
-                "path": path,
-                "category": self.categories[max_idx],
-                "confidence": path_probs[max_idx]
+                "path": key,
+                "category": categories[max_idx],
+                "confidence": path_probs[max_idx],
+                "scores": [
+                    {"category": category, "score": score}
+                    for category, score in zip(categories, path_probs)
+                ],
+                "taxonomy_version": taxonomy_version,
             })
 
         return results
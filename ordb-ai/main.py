@@ -1,23 +1,51 @@
-from fastapi import FastAPI, HTTPException
+import os
+import secrets
+from fastapi import Depends, FastAPI, File, Form, HTTPException, Header, UploadFile
 from pydantic import BaseModel
-from typing import List
-from classifier import get_classifier
+from typing import List, Optional
+from starlette.concurrency import run_in_threadpool
+from classifier import get_classifier, TAXONOMY_VERSION
 
-app = FastAPI(title="orDB AI Microservice")
+# When set, every request must carry "Authorization: Bearer <token>"
+API_TOKEN = os.environ.get("ORDB_AI_TOKEN")
+
+def require_token(authorization: Optional[str] = Header(default=None)):
+    if API_TOKEN and not secrets.compare_digest(authorization or "", f"Bearer {API_TOKEN}"):
+        raise HTTPException(status_code=401, detail="Invalid or missing token")
+
+app = FastAPI(title="orDB AI Microservice", dependencies=[Depends(require_token)])
 
 model_ready = False
 
-class ClassifyRequest(BaseModel):
+class TaxonomyEntry(BaseModel):
+    name: str
+    prompt: str
+
+class TaxonomySpec(BaseModel):
+    # Client-supplied categories; the built-in TAXONOMY is used when omitted
+    taxonomy: Optional[List[TaxonomyEntry]] = None
+    taxonomy_version: Optional[str] = None
+
+class ClassifyRequest(TaxonomySpec):
     images: List[str]
 
+class Score(BaseModel):
+    category: str
+    score: float
+
 class ClassifyResult(BaseModel):
     path: str
     category: str
     confidence: float
+    scores: List[Score] = []
+    taxonomy_version: Optional[str] = None
 
 class BatchClassifyResponse(BaseModel):
     results: List[ClassifyResult]
 
+def taxonomy_pairs(taxonomy):
+    return [(entry.name, entry.prompt) for entry in taxonomy] if taxonomy else None
+
 @app.on_event("startup")
 async def startup_event():
     global model_ready
@@ -27,14 +55,35 @@ async def startup_event():
 @app.get("/health")
 def health_check():
     if model_ready:
-        return {"status": "ready"}
+        classifier = get_classifier()
+        return {
+            "status": "ready",
+            "model": classifier.model_name,
+            "model_version": classifier.model_version,
+            "taxonomy_version": TAXONOMY_VERSION,
+        }
     return {"status": "loading"}
 
 @app.post("/classify/batch", response_model=BatchClassifyResponse)
 def classify_batch(req: ClassifyRequest):
     classifier = get_classifier()
     try:
-        results = classifier.classify_batch(req.images)
+        results = classifier.classify_batch(req.images, taxonomy_pairs(req.taxonomy), req.taxonomy_version)
+        return {"results": results}
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+@app.post("/classify/upload", response_model=BatchClassifyResponse)
+async def classify_upload(images: List[UploadFile] = File(...), taxonomy: Optional[str] = Form(None)):
+    # Each part's filename is echoed back as `path` so the client can match results
+    uploads = [(f.filename, await f.read()) for f in images]
+    # The taxonomy part is a TaxonomySpec as JSON
+    spec = TaxonomySpec.model_validate_json(taxonomy) if taxonomy else TaxonomySpec()
+    classifier = get_classifier()
+    try:
+        results = await run_in_threadpool(
+            classifier.classify_uploads, uploads, taxonomy_pairs(spec.taxonomy), spec.taxonomy_version
+        )
         return {"results": results}
     except Exception as e:
         raise HTTPException(status_code=500, detail=str(e))
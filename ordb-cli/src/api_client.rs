@@ -1,11 +1,90 @@
-use reqwest::Client;
+use clap::ValueEnum;
+use reqwest::{header, multipart, Certificate, Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use anyhow::Context;
 use log::{info, warn};
 
+/// How to reach the AI microservice.
+pub struct ServiceOptions {
+    pub connect_timeout: Duration,
+    /// Whole-request limit, including inference time for a batch.
+    pub request_timeout: Duration,
+    pub token: Option<String>,
+    /// Extra PEM root certificate, for services behind a private CA.
+    pub ca_cert: Option<PathBuf>,
+    pub insecure: bool,
+}
+
+pub fn build_client(options: &ServiceOptions) -> anyhow::Result<Client> {
+    let mut builder = Client::builder()
+        .connect_timeout(options.connect_timeout)
+        .timeout(options.request_timeout);
+
+    if let Some(token) = &options.token {
+        let mut value = header::HeaderValue::from_str(&format!("Bearer {}", token))
+            .context("AI token contains characters not allowed in an HTTP header")?;
+        value.set_sensitive(true);
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value);
+        builder = builder.default_headers(headers);
+    }
+    if let Some(path) = &options.ca_cert {
+        let pem = std::fs::read(path).with_context(|| format!("Could not read CA certificate {:?}", path))?;
+        let cert = Certificate::from_pem(&pem).with_context(|| format!("Invalid PEM certificate {:?}", path))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    if options.insecure {
+        warn!("TLS certificate verification is disabled for the AI microservice.");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
+}
+
+/// One category the service may assign, with the text prompt the model matches images against.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TaxonomyEntry {
+    pub name: String,
+    pub prompt: String,
+}
+
+/// The categories sent with every request. `version` changes whenever a name or prompt does,
+/// so results (and cached classifications) can be tied to the taxonomy that produced them.
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    pub entries: Vec<TaxonomyEntry>,
+    pub version: String,
+}
+
+impl Taxonomy {
+    pub fn new(entries: Vec<TaxonomyEntry>) -> Taxonomy {
+        let mut hasher = blake3::Hasher::new();
+        for entry in &entries {
+            // Length prefixes keep ("ab", "c") and ("a", "bc") apart
+            for field in [&entry.name, &entry.prompt] {
+                hasher.update(&(field.len() as u64).to_le_bytes());
+                hasher.update(field.as_bytes());
+            }
+        }
+        let version = hasher.finalize().to_hex()[..16].to_string();
+        Taxonomy { entries, version }
+    }
+}
+
 #[derive(Serialize)]
-pub struct ClassifyRequest {
+pub struct ClassifyRequest<'a> {
     pub images: Vec<String>, // Absolute paths
+    pub taxonomy: &'a [TaxonomyEntry],
+    pub taxonomy_version: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Score {
+    pub category: String,
+    pub score: f32,
 }
 
 #[derive(Deserialize, Debug)]
@@ -13,6 +92,12 @@ pub struct ClassifyResult {
     pub path: String,
     pub category: String,
     pub confidence: f32,
+    /// Score for every category in the taxonomy; older services omit it
+    #[serde(default)]
+    pub scores: Vec<Score>,
+    /// Echo of the request's taxonomy version; missing when the service ignored the taxonomy
+    #[serde(default)]
+    pub taxonomy_version: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -23,24 +108,50 @@ pub struct BatchClassifyResponse {
 #[derive(Deserialize)]
 struct HealthResponse {
     status: String,
+    #[serde(flatten)]
+    info: ServiceInfo,
 }
 
-pub async fn warm_up(client: &Client, url: &str) -> anyhow::Result<()> {
+/// What the service reports about the model answering. Together with the client's taxonomy
+/// version it keys cached classifications.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ServiceInfo {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub model_version: Option<String>,
+}
+
+impl ServiceInfo {
+    /// Results can only be reused when the service says which model produced them.
+    pub fn cacheable(&self) -> bool {
+        self.model.is_some()
+    }
+}
+
+pub async fn warm_up(client: &Client, url: &str) -> anyhow::Result<ServiceInfo> {
     info!("Waiting for AI microservice to be ready at {}/health...", url);
     let max_retries = 30;
     for attempt in 1..=max_retries {
-        match client.get(&format!("{}/health", url)).send().await {
+        match client.get(format!("{}/health", url)).send().await {
+            // Retrying will not fix credentials
+            Ok(resp) if resp.status() == StatusCode::UNAUTHORIZED || resp.status() == StatusCode::FORBIDDEN => {
+                anyhow::bail!("AI microservice at {} rejected the request ({}); check --ai-token", url, resp.status());
+            }
             Ok(resp) if resp.status().is_success() => {
                 if let Ok(body) = resp.json::<HealthResponse>().await {
                     if body.status == "ready" {
-                        info!("AI microservice is ready.");
-                        return Ok(());
+                        info!("AI microservice is ready ({:?}).", body.info);
+                        return Ok(body.info);
                     }
                     warn!("Microservice loading (status: {}), retry {}/{}...", body.status, attempt, max_retries);
                 }
             }
-            _ => {
-                warn!("Microservice not reachable, retry {}/{}...", attempt, max_retries);
+            Ok(resp) => {
+                warn!("Microservice answered {}, retry {}/{}...", resp.status(), attempt, max_retries);
+            }
+            Err(e) => {
+                warn!("Microservice not reachable ({}), retry {}/{}...", e, attempt, max_retries);
             }
         }
         tokio::time::sleep(Duration::from_secs(2)).await;
@@ -48,13 +159,292 @@ pub async fn warm_up(client: &Client, url: &str) -> anyhow::Result<()> {
     anyhow::bail!("AI microservice did not become ready after {} retries ({}s)", max_retries, max_retries * 2);
 }
 
-pub async fn classify_batch(client: &Client, url: &str, paths: Vec<String>) -> anyhow::Result<Vec<ClassifyResult>> {
-    let req = ClassifyRequest { images: paths };
-    let resp = client.post(&format!("{}/classify/batch", url))
+pub async fn classify_batch(client: &Client, url: &str, paths: Vec<String>, taxonomy: &Taxonomy) -> anyhow::Result<Vec<ClassifyResult>> {
+    let req = ClassifyRequest { images: paths, taxonomy: &taxonomy.entries, taxonomy_version: &taxonomy.version };
+    let resp = client.post(format!("{}/classify/batch", url))
         .json(&req)
         .send()
-        .await?;
-        
+        .await?
+        .error_for_status()?;
+
     let batch_resp: BatchClassifyResponse = resp.json().await?;
     Ok(batch_resp.results)
 }
+
+/// Posts already-read images as multipart parts named `images`. Each part's file name is its
+/// index in `payloads`, which the service echoes back as `path` so results map to source paths.
+/// The taxonomy travels as a JSON text part named `taxonomy`.
+pub async fn classify_upload(client: &Client, url: &str, payloads: &[(String, Vec<u8>)], taxonomy: &Taxonomy) -> anyhow::Result<Vec<ClassifyResult>> {
+    let taxonomy_json = serde_json::json!({ "taxonomy": taxonomy.entries, "taxonomy_version": taxonomy.version });
+    let mut form = multipart::Form::new().text("taxonomy", taxonomy_json.to_string());
+    for (idx, (_, data)) in payloads.iter().enumerate() {
+        let part = multipart::Part::bytes(data.clone()).file_name(idx.to_string());
+        form = form.part("images", part);
+    }
+    let resp = client.post(format!("{}/classify/upload", url))
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let batch_resp: BatchClassifyResponse = resp.json().await?;
+    let mut results = Vec::with_capacity(batch_resp.results.len());
+    for mut res in batch_resp.results {
+        let idx: usize = res.path.parse().map_err(|_| anyhow::anyhow!("Unexpected result name {:?}", res.path))?;
+        let (path, _) = payloads.get(idx).ok_or_else(|| anyhow::anyhow!("Unexpected result index {}", idx))?;
+        res.path = path.clone();
+        results.push(res);
+    }
+    Ok(results)
+}
+
+/// How images reach the AI microservice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum UploadMode {
+    /// Send local paths; the service must see the same filesystem
+    Paths,
+    /// Upload a JPEG thumbnail of each image
+    Thumbnail,
+    /// Upload each file unchanged
+    Bytes,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Upload {
+    pub mode: UploadMode,
+    /// Longest side, in pixels, of uploaded thumbnails
+    pub thumbnail_size: u32,
+}
+
+/// Everything about a classification request besides the images themselves.
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    pub upload: Upload,
+    pub taxonomy: Arc<Taxonomy>,
+    /// Attempts after the first before a batch is split
+    pub retries: u32,
+}
+
+/// Reads every image of a batch once, so retries and split halves reuse the same bytes.
+/// Images that cannot be read or decoded are returned as failures.
+async fn prepare_uploads(paths: Vec<String>, upload: Upload) -> (Vec<(String, Vec<u8>)>, Vec<(String, String)>) {
+    let task = tokio::task::spawn_blocking(move || {
+        let mut payloads = Vec::new();
+        let mut failures = Vec::new();
+        for path in paths {
+            let data = match upload.mode {
+                UploadMode::Thumbnail => thumbnail(&path, upload.thumbnail_size),
+                _ => std::fs::read(&path).map_err(anyhow::Error::from),
+            };
+            match data {
+                Ok(data) => payloads.push((path, data)),
+                Err(e) => failures.push((path, format!("Could not prepare upload: {}", e))),
+            }
+        }
+        (payloads, failures)
+    });
+    task.await.unwrap_or_default()
+}
+
+fn thumbnail(path: &str, size: u32) -> anyhow::Result<Vec<u8>> {
+    let img = image::open(path)?.thumbnail(size, size).to_rgb8();
+    let mut out = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(img).write_to(&mut out, image::ImageOutputFormat::Jpeg(85))?;
+    Ok(out.into_inner())
+}
+
+/// Outcome of `classify_resilient`: results for every image the service classified, and an
+/// error message for every image it could not.
+#[derive(Default)]
+pub struct BatchOutcome {
+    pub results: Vec<ClassifyResult>,
+    pub failures: Vec<(String, String)>,
+}
+
+/// Classifies `paths`, retrying each request up to `options.retries` times with exponential backoff
+/// (`base_delay`, doubled per attempt). A batch the service still answers with an error status is
+/// split in half and each half tried once on its own, so a single unreadable image cannot sink the
+/// whole batch; an image that fails alone, or that the service leaves out of its answer, is
+/// reported in `failures`. Halves are not retried again: the retries already showed the failure is
+/// not transient. When the service cannot be reached, times out or rejects the credentials,
+/// smaller batches would fail the same way, so every image still pending fails at once.
+pub async fn classify_resilient(
+    client: &Client,
+    url: &str,
+    paths: Vec<String>,
+    options: &BatchOptions,
+    base_delay: Duration,
+) -> BatchOutcome {
+    let (upload, taxonomy) = (options.upload, options.taxonomy.as_ref());
+    let mut outcome = BatchOutcome::default();
+    // Paths mode carries no bytes
+    let payloads = if upload.mode == UploadMode::Paths {
+        paths.into_iter().map(|p| (p, Vec::new())).collect()
+    } else {
+        let (payloads, failures) = prepare_uploads(paths, upload).await;
+        outcome.failures = failures;
+        payloads
+    };
+
+    let mut pending = vec![(payloads, options.retries)];
+    while let Some((batch, retries)) = pending.pop() {
+        if batch.is_empty() {
+            continue;
+        }
+        let mut attempt = 0;
+        let result = loop {
+            let sent = match upload.mode {
+                UploadMode::Paths => classify_batch(client, url, batch.iter().map(|(p, _)| p.clone()).collect(), taxonomy).await,
+                _ => classify_upload(client, url, &batch, taxonomy).await,
+            };
+            match sent {
+                Ok(results) => break Ok(results),
+                // Retrying will not fix credentials
+                Err(e) if attempt >= retries || rejected_credentials(&e) => break Err(e),
+                Err(e) => {
+                    let delay = base_delay * 2u32.saturating_pow(attempt);
+                    warn!("Batch of {} failed ({}), retrying in {:?}...", batch.len(), e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        };
+
+        match result {
+            Ok(results) => {
+                for (path, _) in &batch {
+                    if !results.iter().any(|r| &r.path == path) {
+                        outcome.failures.push((path.clone(), "Not returned by the AI microservice".to_string()));
+                    }
+                }
+                outcome.results.extend(results);
+            }
+            Err(e) if !answered_with_error(&e) => {
+                let message = e.to_string();
+                let rest = pending.drain(..).flat_map(|(batch, _)| batch);
+                outcome.failures.extend(batch.into_iter().chain(rest).map(|(path, _)| (path, message.clone())));
+            }
+            Err(_) if batch.len() > 1 => {
+                let mut first = batch;
+                let second = first.split_off(first.len() / 2);
+                pending.push((second, 0));
+                pending.push((first, 0));
+            }
+            Err(e) => {
+                let path = batch.into_iter().next().map(|(p, _)| p).unwrap_or_default();
+                outcome.failures.push((path, e.to_string()));
+            }
+        }
+    }
+    outcome
+}
+
+/// Whether the service received the batch and answered it with an error status, which a smaller
+/// batch might avoid.
+fn answered_with_error(error: &anyhow::Error) -> bool {
+    let status = error.downcast_ref::<reqwest::Error>().and_then(|e| e.status());
+    status.is_some() && !rejected_credentials(error)
+}
+
+fn rejected_credentials(error: &anyhow::Error) -> bool {
+    let status = error.downcast_ref::<reqwest::Error>().and_then(|e| e.status());
+    status == Some(StatusCode::UNAUTHORIZED) || status == Some(StatusCode::FORBIDDEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves `/classify/batch` on a local port, answering each request with whatever `answer`
+    /// returns for its paths: a status and the results for every path, or `None` to hang up
+    /// without answering. Returns the URL and the number of requests received.
+    async fn serve(answer: impl Fn(&[String]) -> Option<u16> + Send + Sync + 'static) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (answer, counter) = (Arc::new(answer), requests.clone());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (answer, counter) = (answer.clone(), counter.clone());
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let body = loop {
+                        let n = socket.read(&mut buf).await.unwrap_or(0);
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head.lines()
+                                .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                                .unwrap_or(0);
+                            if body.len() >= length {
+                                break body.to_string();
+                            }
+                        }
+                    };
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let paths: Vec<String> = serde_json::from_str::<serde_json::Value>(&body).unwrap()["images"]
+                        .as_array().unwrap().iter().map(|p| p.as_str().unwrap().to_string()).collect();
+                    let Some(status) = answer(&paths) else { return };
+                    let results: Vec<_> = paths.iter()
+                        .map(|p| serde_json::json!({ "path": p, "category": "Arte", "confidence": 0.9 }))
+                        .collect();
+                    let body = serde_json::json!({ "results": results }).to_string();
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status, body.len(), body,
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    async fn classify(url: &str, retries: u32) -> BatchOutcome {
+        let paths = ["a", "b", "bad", "d"].map(String::from).to_vec();
+        let options = BatchOptions {
+            upload: Upload { mode: UploadMode::Paths, thumbnail_size: 0 },
+            taxonomy: Arc::new(crate::config::Config::default().taxonomy()),
+            retries,
+        };
+        classify_resilient(&Client::new(), url, paths, &options, Duration::from_millis(1)).await
+    }
+
+    #[tokio::test]
+    async fn test_classify_resilient_retries_then_splits() {
+        // Fails once, then succeeds
+        let failed_once = AtomicUsize::new(0);
+        let (url, requests) = serve(move |_| Some(if failed_once.fetch_add(1, Ordering::SeqCst) == 0 { 503 } else { 200 })).await;
+        let outcome = classify(&url, 1).await;
+        assert_eq!((outcome.results.len(), outcome.failures.len()), (4, 0));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Every batch holding "bad" fails: 2 attempts, then the halves, then the quarters of the bad half
+        let (url, requests) = serve(|paths| Some(if paths.iter().any(|p| p == "bad") { 500 } else { 200 })).await;
+        let outcome = classify(&url, 1).await;
+        assert_eq!(outcome.results.len(), 3);
+        assert_eq!(outcome.failures.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), ["bad"]);
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_classify_resilient_never_splits_without_an_answer() {
+        // Hangs up every time: retried, never split
+        let (url, requests) = serve(|_| None).await;
+        let outcome = classify(&url, 2).await;
+        assert_eq!((outcome.results.len(), outcome.failures.len()), (0, 4));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // Rejected credentials: neither retried nor split
+        let (url, requests) = serve(|_| Some(401)).await;
+        let outcome = classify(&url, 2).await;
+        assert_eq!((outcome.results.len(), outcome.failures.len()), (0, 4));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
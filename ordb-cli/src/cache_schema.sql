@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS classification_cache (
+    blake3_hash      TEXT NOT NULL,
+    model            TEXT NOT NULL,
+    model_version    TEXT NOT NULL,
+    taxonomy_version TEXT NOT NULL,
+    category         TEXT NOT NULL,
+    confidence       REAL NOT NULL,
+    scores           TEXT,
+    created_at       DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (blake3_hash, model, model_version, taxonomy_version)
+);
+
+-- Small facts remembered between runs, e.g. the last model identity the service reported
+CREATE TABLE IF NOT EXISTS meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,193 @@
+//! EN:
+//! Image classification backends behind one `Classifier` trait: the AI microservice over HTTP,
+//! the deterministic rules of `rules.rs`, and a mock that answers from file contents so Phase 3
+//! can be exercised without Python or a GPU. `[classifier] backend` in the config picks one;
+//! the rules also stand in whenever the chosen backend is unavailable.
+//!
+//! ES:
+//! Backends de clasificación de imágenes detrás de un único trait `Classifier`: el microservicio
+//! de IA por HTTP, las reglas deterministas de `rules.rs` y un simulador que responde a partir del
+//! contenido de los archivos, para probar la Fase 3 sin Python ni GPU. `[classifier] backend` en
+//! la configuración elige uno; las reglas lo sustituyen cuando el elegido no está disponible.
+
+use crate::api_client::{self, BatchOptions, BatchOutcome, ClassifyResult, Score, ServiceInfo};
+use crate::rules;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// Which `Classifier` the pipeline uses, from `[classifier] backend`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Http,
+    Rules,
+    Mock,
+}
+
+#[async_trait]
+pub trait Classifier: Send + Sync {
+    /// Waits until the backend can answer and reports which model does. An error means it is
+    /// unavailable for this run.
+    async fn warm_up(&self) -> anyhow::Result<ServiceInfo>;
+
+    /// Classifies one batch. Never fails as a whole: images that could not be classified are
+    /// listed in the outcome's failures.
+    async fn classify(&self, paths: Vec<String>, options: &BatchOptions) -> BatchOutcome;
+
+    /// What `category_source` records for this backend's answers.
+    fn source(&self) -> &'static str {
+        "IA"
+    }
+}
+
+/// The AI microservice. Requests are retried and split as described in
+/// `api_client::classify_resilient`.
+pub struct Http {
+    client: Client,
+    url: String,
+    retry_delay: Duration,
+}
+
+impl Http {
+    pub fn new(client: Client, url: &str) -> Http {
+        Http { client, url: url.trim_end_matches('/').to_string(), retry_delay: Duration::from_millis(500) }
+    }
+}
+
+#[async_trait]
+impl Classifier for Http {
+    async fn warm_up(&self) -> anyhow::Result<ServiceInfo> {
+        api_client::warm_up(&self.client, &self.url).await
+    }
+
+    async fn classify(&self, paths: Vec<String>, options: &BatchOptions) -> BatchOutcome {
+        api_client::classify_resilient(&self.client, &self.url, paths, options, self.retry_delay).await
+    }
+}
+
+/// Filename and EXIF rules. Images no rule recognizes get `unknown`, the locale's folder name
+/// for unclassified images. Rules do not follow a custom taxonomy.
+pub struct Rules {
+    unknown: &'static str,
+}
+
+impl Rules {
+    pub fn new(unknown: &'static str) -> Rules {
+        Rules { unknown }
+    }
+}
+
+#[async_trait]
+impl Classifier for Rules {
+    async fn warm_up(&self) -> anyhow::Result<ServiceInfo> {
+        Ok(ServiceInfo::default())
+    }
+
+    async fn classify(&self, paths: Vec<String>, _options: &BatchOptions) -> BatchOutcome {
+        let unknown = self.unknown;
+        let task = tokio::task::spawn_blocking(move || {
+            let results = paths.into_iter().map(|path| {
+                let file = Path::new(&path);
+                let mime = infer::get_from_path(file).ok().flatten().map(|m| m.mime_type()).unwrap_or("");
+                let dimensions = image::image_dimensions(file).ok();
+                let category = rules::classify(file, mime, dimensions).unwrap_or(unknown);
+                ClassifyResult {
+                    category: category.to_string(),
+                    confidence: 1.0,
+                    scores: Vec::new(),
+                    taxonomy_version: None,
+                    path,
+                }
+            });
+            BatchOutcome { results: results.collect(), failures: Vec::new() }
+        });
+        task.await.unwrap_or_default()
+    }
+
+    fn source(&self) -> &'static str {
+        "REGLAS"
+    }
+}
+
+/// Deterministic stand-in for the AI: the category is picked from the file's BLAKE3 hash, so
+/// copies of an image always agree. Unreadable files fail like they would on the service.
+pub struct Mock;
+
+#[async_trait]
+impl Classifier for Mock {
+    async fn warm_up(&self) -> anyhow::Result<ServiceInfo> {
+        Ok(ServiceInfo { model: Some("mock".to_string()), model_version: Some("1".to_string()) })
+    }
+
+    async fn classify(&self, paths: Vec<String>, options: &BatchOptions) -> BatchOutcome {
+        let taxonomy = options.taxonomy.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut outcome = BatchOutcome::default();
+            for path in paths {
+                match std::fs::read(&path) {
+                    Ok(data) => outcome.results.push(mock_result(path, &data, &taxonomy)),
+                    Err(e) => outcome.failures.push((path, e.to_string())),
+                }
+            }
+            outcome
+        });
+        task.await.unwrap_or_default()
+    }
+}
+
+fn mock_result(path: String, data: &[u8], taxonomy: &api_client::Taxonomy) -> ClassifyResult {
+    let count = taxonomy.entries.len().max(1);
+    let top = blake3::hash(data).as_bytes()[0] as usize % count;
+    let rest = if count > 1 { 0.1 / (count - 1) as f32 } else { 0.0 };
+    let scores: Vec<Score> = taxonomy.entries.iter().enumerate()
+        .map(|(i, entry)| Score {
+            category: entry.name.clone(),
+            score: if i == top { 1.0 - rest * (count - 1) as f32 } else { rest },
+        })
+        .collect();
+    ClassifyResult {
+        category: scores.get(top).map(|s| s.category.clone()).unwrap_or_default(),
+        confidence: scores.get(top).map_or(1.0, |s| s.score),
+        taxonomy_version: Some(taxonomy.version.clone()),
+        scores,
+        path,
+    }
+}
+
+/// Classifies `paths` in the background, keeping up to `concurrency` batches in flight, and
+/// yields each batch's outcome as soon as it completes (not in submission order). The channel
+/// closes once every batch has been answered.
+pub fn spawn_classification(
+    classifier: Arc<dyn Classifier>,
+    paths: Vec<String>,
+    batch_size: usize,
+    concurrency: usize,
+    options: BatchOptions,
+) -> mpsc::Receiver<BatchOutcome> {
+    let concurrency = concurrency.max(1);
+    let (tx, rx) = mpsc::channel(concurrency);
+    let batches: Vec<Vec<String>> = paths.chunks(batch_size.max(1)).map(|c| c.to_vec()).collect();
+
+    tokio::spawn(async move {
+        let mut in_flight = JoinSet::new();
+        for batch in batches {
+            if in_flight.len() >= concurrency {
+                in_flight.join_next().await;
+            }
+            let (classifier, tx, options) = (classifier.clone(), tx.clone(), options.clone());
+            in_flight.spawn(async move {
+                let outcome = classifier.classify(batch, &options).await;
+                let _ = tx.send(outcome).await;
+            });
+        }
+        while in_flight.join_next().await.is_some() {}
+    });
+    rx
+}
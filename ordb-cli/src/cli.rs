@@ -1,5 +1,8 @@
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use crate::api_client::UploadMode;
+use crate::i18n::Locale;
+use crate::policy::PrimaryRule;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "File Organizer CLI", long_about = None)]
@@ -15,6 +18,58 @@ pub struct Cli {
     #[arg(short, long)]
     pub destination: Option<PathBuf>,
 
+    /// Language for destination folder names and messages
+    #[arg(long, value_enum, global = true, default_value_t = Locale::Es)]
+    pub locale: Locale,
+
+    /// TOML configuration file (destination layout templates, ...)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Classify images with filename/EXIF rules instead of the AI microservice
+    #[arg(long)]
+    pub no_ai: bool,
+
+    /// Base URL of the AI microservice
+    #[arg(long, default_value = "http://127.0.0.1:8000")]
+    pub ai_url: String,
+
+    /// Seconds to wait for a connection to the AI microservice
+    #[arg(long, default_value_t = 10)]
+    pub ai_connect_timeout: u64,
+
+    /// Seconds to wait for a whole AI request, including batch inference
+    #[arg(long, default_value_t = 300)]
+    pub ai_timeout: u64,
+
+    /// Bearer token sent to the AI microservice
+    #[arg(long, env = "ORDB_AI_TOKEN", hide_env_values = true)]
+    pub ai_token: Option<String>,
+
+    /// Extra PEM root certificate trusted for the AI microservice
+    #[arg(long)]
+    pub ai_ca_cert: Option<PathBuf>,
+
+    /// Accept invalid TLS certificates from the AI microservice (testing only)
+    #[arg(long)]
+    pub ai_insecure: bool,
+
+    /// Retries per classification batch before it is split to isolate failing images
+    #[arg(long, default_value_t = 3)]
+    pub ai_retries: u32,
+
+    /// How images reach the AI microservice: local paths, uploaded thumbnails or uploaded files
+    #[arg(long, value_enum, default_value_t = UploadMode::Paths)]
+    pub ai_upload: UploadMode,
+
+    /// Longest side, in pixels, of thumbnails sent with --ai-upload thumbnail
+    #[arg(long, default_value_t = 512)]
+    pub thumbnail_size: u32,
+
+    /// Classification batches in flight at once
+    #[arg(long, default_value_t = 2)]
+    pub ai_concurrency: usize,
+
     /// Batch size for AI inference
     #[arg(long, default_value_t = 64)]
     pub batch_size: usize,
@@ -30,14 +85,58 @@ pub struct Cli {
     /// Resume from existing state.db
     #[arg(long)]
     pub resume: bool,
+
+    /// Number of hashing threads (defaults to the number of CPU cores)
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Compare exact duplicates byte-by-byte against their primary before marking them
+    #[arg(long)]
+    pub paranoid: bool,
+
+    /// Rules, in priority order, that decide which copy of a duplicate group stays primary
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [PrimaryRule::PreferredSource, PrimaryRule::RichestExif, PrimaryRule::OldestMtime, PrimaryRule::ShortestPath]
+    )]
+    pub primary_policy: Vec<PrimaryRule>,
+
+    /// Directories whose copies win under the preferred-source rule, most preferred first
+    #[arg(long, num_args = 1..)]
+    pub prefer_source: Vec<PathBuf>,
+
+    /// Group visually similar images (resized, re-compressed, re-saved) as DUPLICADO_VISUAL
+    #[arg(long)]
+    pub visual_dedup: bool,
+
+    /// Maximum perceptual hash distance (0-64) for two images to count as visual duplicates
+    #[arg(long, default_value_t = 6)]
+    pub visual_threshold: u32,
+
+    /// Files of at least this many MiB are hashed with multithreaded mmap BLAKE3 (0 = disabled)
+    #[arg(long, default_value_t = 0)]
+    pub large_file_mb: u64,
+
+    /// Extra attempts for a staged copy whose BLAKE3 does not match its source
+    #[arg(long, default_value_t = 2)]
+    pub copy_retries: u32,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Commit the organization (move originals to trash)
-    Commit,
+    Commit {
+        /// Move only files whose copy reached the destination and was verified, instead of
+        /// whole source directories
+        #[arg(long)]
+        per_file: bool,
+    },
     /// Rollback the organization (delete destination)
     Rollback,
+    /// Undo a commit (move originals back from the trash)
+    Restore,
     /// Purge the trash
     Purge {
         #[arg(long)]
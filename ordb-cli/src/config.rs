@@ -0,0 +1,142 @@
+//! EN:
+//! Optional TOML configuration file (`--config`). Every section has built-in defaults, so an
+//! empty file, or no file at all, reproduces the standard layout.
+//!
+//! ES:
+//! Archivo de configuración TOML opcional (`--config`). Cada sección tiene valores por defecto,
+//! así que un archivo vacío, o ninguno, reproduce la estructura estándar.
+
+use std::fs;
+use std::path::Path;
+use anyhow::Context;
+use serde::Deserialize;
+use crate::api_client::{Taxonomy, TaxonomyEntry};
+use crate::classifier::Backend;
+use crate::i18n::Catalog;
+use crate::template::Template;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub layout: LayoutConfig,
+    /// Categories sent to the AI microservice, as `[[taxonomy]]` tables with `name` and `prompt`.
+    /// A config that lists any replaces the whole default set.
+    pub taxonomy: Vec<TaxonomyEntry>,
+    pub classifier: ClassifierConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            layout: LayoutConfig::default(),
+            taxonomy: default_taxonomy(),
+            classifier: ClassifierConfig::default(),
+        }
+    }
+}
+
+fn default_taxonomy() -> Vec<TaxonomyEntry> {
+    [
+        ("Persona_Sola", "A photo of a single person, portrait, selfie"),
+        ("Grupo_Personas", "A photo of a group of people, gathering, celebration"),
+        ("Documentos_Texto", "A photo of a document, receipt, text, scan, screenshot"),
+        ("Paisajes_Naturaleza", "A photo of a natural landscape, outdoors, nature"),
+        ("Vehiculos_Motor", "A photo of a car, motorcycle, truck, vehicle"),
+        ("Mascotas_Animales", "A photo of a dog, cat, pet, animal"),
+        ("Comida_Bebida", "A photo of food, meal, drink"),
+        ("Arquitectura_Espacios", "A photo of a building, architecture, indoor room"),
+        ("Arte_Graficos", "Digital art, meme, vector graphic, screenshot of software"),
+    ]
+    .into_iter()
+    .map(|(name, prompt)| TaxonomyEntry { name: name.to_string(), prompt: prompt.to_string() })
+    .collect()
+}
+
+/// `backend` is `http` (the AI microservice, default), `rules` (same as `--no-ai`) or `mock`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassifierConfig {
+    pub backend: Backend,
+}
+
+/// Destination templates per MIME family, relative to `--destination`. Families left out use
+/// the standard layout, with fallback folder names taken from the locale.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub image: Option<String>,
+    pub video: Option<String>,
+    pub audio: Option<String>,
+    pub other: Option<String>,
+}
+
+pub struct Layout {
+    pub image: Template,
+    pub video: Template,
+    pub audio: Template,
+    pub other: Template,
+    pub catalog: &'static Catalog,
+}
+
+impl Layout {
+    pub fn uses(&self, variable: &str) -> bool {
+        [&self.image, &self.video, &self.audio, &self.other].iter().any(|t| t.uses(variable))
+    }
+}
+
+impl Config {
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Config> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read config file {:?}", path))?;
+        let config: Config = toml::from_str(&text)
+            .with_context(|| format!("Invalid config file {:?}", path))?;
+        // Surface template and taxonomy errors before any work starts
+        config.layout(&crate::i18n::ES)?;
+        config.validate_taxonomy().with_context(|| format!("Invalid taxonomy in {:?}", path))?;
+        Ok(config)
+    }
+
+    fn validate_taxonomy(&self) -> anyhow::Result<()> {
+        if self.taxonomy.is_empty() {
+            anyhow::bail!("at least one [[taxonomy]] entry is required");
+        }
+        let mut seen = std::collections::HashSet::new();
+        for entry in &self.taxonomy {
+            if entry.name.trim().is_empty() || entry.prompt.trim().is_empty() {
+                anyhow::bail!("taxonomy entries need a non-empty name and prompt");
+            }
+            if !seen.insert(entry.name.as_str()) {
+                anyhow::bail!("category {:?} is listed twice", entry.name);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn taxonomy(&self) -> Taxonomy {
+        Taxonomy::new(self.taxonomy.clone())
+    }
+
+    pub fn layout(&self, catalog: &'static Catalog) -> anyhow::Result<Layout> {
+        let visual = "{media}/{date_status}/{year}/{month:02}_{month_name}/{category}/{filename}".to_string();
+        // The album level only appears when there is one; an album without an artist is the one
+        // case where this differs from the fixed layout, which dropped the album then
+        let audio = format!("{{media}}/{{artist|{}}}/{{album}}/{{filename}}", catalog.unknown);
+        let other = format!("{{media}}/{{ext|{}}}/{{filename}}", catalog.no_extension);
+
+        let parse = |family: &str, configured: &Option<String>, default: String| {
+            let source = configured.clone().unwrap_or(default);
+            Template::parse(&source).with_context(|| format!("Invalid layout.{} template", family))
+        };
+        Ok(Layout {
+            image: parse("image", &self.layout.image, visual.clone())?,
+            video: parse("video", &self.layout.video, visual)?,
+            audio: parse("audio", &self.layout.audio, audio)?,
+            other: parse("other", &self.layout.other, other)?,
+            catalog,
+        })
+    }
+}
@@ -6,10 +6,95 @@ pub fn init_db<P: AsRef<Path>>(db_path: P) -> Result<Connection> {
     
     let schema = include_str!("schema.sql");
     conn.execute_batch(schema)?;
+    migrate(&conn)?;
+    // Created here rather than in schema.sql so it also reaches databases made before it existed
+    let indexed: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_dest_path')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !indexed {
+        release_shared_dest_paths(&conn)?;
+        conn.execute_batch("CREATE UNIQUE INDEX idx_dest_path ON files(dest_path);")?;
+    }
     
     Ok(conn)
 }
 
+/// Versions without the unique index could give several files the same destination, the last
+/// copy overwriting the others. None of them can be trusted to own that path, so all of them go
+/// back to being projected and staged by the next `--resume`.
+fn release_shared_dest_paths(conn: &Connection) -> Result<()> {
+    let released = conn.execute(
+        "UPDATE files SET dest_path = NULL, sanitized_components = NULL,
+            status = CASE WHEN status IN ('COMPLETADO', 'ERROR', 'ERROR_VERIFICACION') THEN 'PRIMARIO' ELSE status END
+         WHERE dest_path IN (SELECT dest_path FROM files WHERE dest_path IS NOT NULL
+                             GROUP BY dest_path HAVING COUNT(*) > 1)",
+        [],
+    )?;
+    if released > 0 {
+        log::warn!("{} files shared a destination path with another file; they will be staged again on --resume", released);
+    }
+    Ok(())
+}
+
+/// Columns added after a table was first created. `CREATE TABLE IF NOT EXISTS` leaves an
+/// existing state.db as it was, so `--resume`, `commit`, `rollback` and `restore` on a database
+/// from an older version add whatever it is missing. The last field fills the new column for rows
+/// that already existed, when its default would misdescribe them.
+const ADDED_COLUMNS: &[(&str, &str, &str, Option<&str>)] = &[
+    ("files", "partial_hash", "TEXT", None),
+    // Older versions fully hashed every file they could read
+    ("files", "hash_level", "TEXT NOT NULL DEFAULT 'NINGUNO'",
+        Some("UPDATE files SET hash_level = 'COMPLETO' WHERE blake3_hash IS NOT NULL")),
+    ("files", "phash", "TEXT", None),
+    ("files", "image_width", "INTEGER", None),
+    ("files", "image_height", "INTEGER", None),
+    ("files", "category_source", "TEXT", None),
+    ("files", "classification_error", "TEXT", None),
+    ("files", "model_name", "TEXT", None),
+    ("files", "model_version", "TEXT", None),
+    ("files", "taxonomy_version", "TEXT", None),
+    ("files", "sanitized_components", "TEXT", None),
+    ("commit_manifest", "restored_at", "DATETIME", None),
+];
+
+fn migrate(conn: &Connection) -> Result<()> {
+    for (table, column, definition, backfill) in ADDED_COLUMNS {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns = stmt.query_map([], |row| row.get::<_, String>(1))?.collect::<Result<Vec<_>>>()?;
+        if !columns.iter().any(|c| c == column) {
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+            if let Some(backfill) = backfill {
+                conn.execute_batch(backfill)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Starts a new, non-resumed run: everything the run recomputes is cleared. The commit manifest is
+/// kept, so originals an earlier `commit` moved to the trash stay restorable however many runs
+/// follow; the files it pointed at are gone, hence the NULL `file_id`.
+pub fn reset(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM commit_manifest WHERE restored_at IS NOT NULL;
+         UPDATE commit_manifest SET file_id = NULL;
+         DELETE FROM file_tags;
+         DELETE FROM files;
+         DELETE FROM sources;
+         DELETE FROM meta;"
+    )
+}
+
+/// The classification cache lives in its own file so it survives the `state.db` reset at the
+/// start of every non-resumed run.
+pub fn init_cache_db<P: AsRef<Path>>(db_path: P) -> Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    conn.execute_batch(include_str!("cache_schema.sql"))?;
+    Ok(conn)
+}
+
 pub fn insert_source(conn: &Connection, path: &str) -> Result<()> {
     conn.execute(
         "INSERT OR IGNORE INTO sources (path) VALUES (?1)",
@@ -24,11 +109,19 @@ pub fn get_sources(conn: &Connection) -> Result<Vec<String>> {
     Ok(sources)
 }
 
-pub fn insert_file(conn: &Connection, path: &str, size: u64, mime: Option<&str>, hash: Option<&str>) -> Result<Option<i64>> {
+pub fn insert_file(
+    conn: &Connection,
+    path: &str,
+    size: u64,
+    mime: Option<&str>,
+    hash: Option<&str>,
+    partial_hash: Option<&str>,
+    hash_level: &str,
+) -> Result<Option<i64>> {
     let rows = conn.execute(
-        "INSERT OR IGNORE INTO files (source_path, file_size, mime_type, blake3_hash, status)
-         VALUES (?1, ?2, ?3, ?4, 'PENDIENTE')",
-        params![path, size, mime, hash],
+        "INSERT OR IGNORE INTO files (source_path, file_size, mime_type, blake3_hash, partial_hash, hash_level, status)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'PENDIENTE')",
+        params![path, size, mime, hash, partial_hash, hash_level],
     )?;
     if rows == 0 {
         // Already existed (resume case), return None to signal skip
@@ -46,6 +139,45 @@ pub fn find_primary_by_hash(conn: &Connection, hash: &str) -> Result<Option<i64>
     ).optional()
 }
 
+pub fn get_source_path(conn: &Connection, id: i64) -> Result<String> {
+    conn.query_row(
+        "SELECT source_path FROM files WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )
+}
+
+pub struct SizeCandidate {
+    pub id: i64,
+    pub source_path: String,
+    pub blake3_hash: Option<String>,
+    pub partial_hash: Option<String>,
+}
+
+pub fn find_primaries_by_size(conn: &Connection, size: u64, exclude_id: i64) -> Result<Vec<SizeCandidate>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path, blake3_hash, partial_hash FROM files
+         WHERE file_size = ?1 AND status = 'PRIMARIO' AND id != ?2"
+    )?;
+    let files = stmt.query_map(params![size, exclude_id], |row| {
+        Ok(SizeCandidate {
+            id: row.get(0)?,
+            source_path: row.get(1)?,
+            blake3_hash: row.get(2)?,
+            partial_hash: row.get(3)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+pub fn set_full_hash(conn: &Connection, id: i64, hash: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET blake3_hash = ?1, hash_level = 'COMPLETO' WHERE id = ?2",
+        params![hash, id],
+    )?;
+    Ok(())
+}
+
 pub fn update_status(conn: &Connection, id: i64, status: &str, primary_id: Option<i64>) -> Result<()> {
     conn.execute(
         "UPDATE files SET status = ?1, primary_id = ?2 WHERE id = ?3",
@@ -54,65 +186,295 @@ pub fn update_status(conn: &Connection, id: i64, status: &str, primary_id: Optio
     Ok(())
 }
 
+pub struct GroupMember {
+    pub id: i64,
+    pub source_path: String,
+    /// The group's current primary; the primary itself included.
+    pub primary_id: i64,
+    pub dest_path: Option<String>,
+}
+
+/// Members of every exact-duplicate group whose primary has not been staged yet, ordered by
+/// group. Groups follow `primary_id`, not the hash: duplicates of a primary an earlier run
+/// already staged never join a new primary with the same content.
+pub fn get_duplicate_groups(conn: &Connection) -> Result<Vec<GroupMember>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.source_path, p.id, f.dest_path
+         FROM files f JOIN files p ON p.id = COALESCE(f.primary_id, f.id)
+         WHERE p.status = 'PRIMARIO' AND f.status IN ('PRIMARIO', 'DUPLICADO_EXACTO')
+           AND EXISTS (SELECT 1 FROM files d WHERE d.primary_id = p.id AND d.status = 'DUPLICADO_EXACTO')
+         ORDER BY p.id, f.id"
+    )?;
+    let files = stmt.query_map([], |row| {
+        Ok(GroupMember {
+            id: row.get(0)?,
+            source_path: row.get(1)?,
+            primary_id: row.get(2)?,
+            dest_path: row.get(3)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// Demoted members lose everything Phase 3 derived for them as a primary, so no stale
+/// `dest_path`, category or tags outlive the change.
+pub fn set_group_primary(conn: &Connection, primary_id: i64, member_ids: &[i64]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for &id in member_ids {
+        if id == primary_id {
+            tx.execute("UPDATE files SET status = 'PRIMARIO', primary_id = NULL WHERE id = ?1", params![id])?;
+        } else {
+            tx.execute(
+                "UPDATE files SET status = 'DUPLICADO_EXACTO', primary_id = ?1,
+                    dest_path = NULL, sanitized_components = NULL, category = NULL, confidence = NULL,
+                    category_source = NULL, classification_error = NULL, model_name = NULL,
+                    model_version = NULL, taxonomy_version = NULL, date_source = NULL,
+                    date_value = NULL, artist = NULL, album = NULL
+                 WHERE id = ?2",
+                params![primary_id, id],
+            )?;
+            tx.execute("DELETE FROM file_tags WHERE file_id = ?1", params![id])?;
+        }
+    }
+    tx.commit()
+}
+
+pub fn update_status_with_error(conn: &Connection, id: i64, status: &str, primary_id: Option<i64>, error_msg: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET status = ?1, primary_id = ?2, error_msg = ?3 WHERE id = ?4",
+        params![status, primary_id, error_msg, id],
+    )?;
+    Ok(())
+}
+
 pub struct PrimaryFile {
     pub id: i64,
     pub source_path: String,
     pub mime_type: Option<String>,
-    pub blake3_hash: String,
+    pub blake3_hash: Option<String>,
+    pub category: Option<String>,
+    pub confidence: Option<f32>,
+    pub category_source: Option<String>,
+    pub classification_error: Option<String>,
+    pub date_source: Option<String>,
+    pub date_value: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub model_name: Option<String>,
+    pub model_version: Option<String>,
+    pub taxonomy_version: Option<String>,
 }
 
 pub fn get_primary_files(conn: &Connection) -> Result<Vec<PrimaryFile>> {
-    let mut stmt = conn.prepare("SELECT id, source_path, mime_type, blake3_hash FROM files WHERE status = 'PRIMARIO'")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path, mime_type, blake3_hash, category, confidence, category_source, classification_error,
+                date_source, date_value, artist, album, model_name, model_version, taxonomy_version
+         FROM files WHERE status = 'PRIMARIO'"
+    )?;
     let files = stmt.query_map([], |row| {
         Ok(PrimaryFile {
             id: row.get(0)?,
             source_path: row.get(1)?,
             mime_type: row.get(2)?,
             blake3_hash: row.get(3)?,
+            category: row.get(4)?,
+            confidence: row.get(5)?,
+            category_source: row.get(6)?,
+            classification_error: row.get(7)?,
+            date_source: row.get(8)?,
+            date_value: row.get(9)?,
+            artist: row.get(10)?,
+            album: row.get(11)?,
+            model_name: row.get(12)?,
+            model_version: row.get(13)?,
+            taxonomy_version: row.get(14)?,
         })
     })?.collect::<Result<Vec<_>>>()?;
     Ok(files)
 }
 
-pub fn update_enrichment(
-    conn: &Connection, 
-    id: i64, 
-    category: Option<&str>, 
-    confidence: Option<f32>, 
-    date_source: Option<&str>, 
-    date_value: Option<&str>, 
-    artist: Option<&str>, 
-    album: Option<&str>,
-    dest_path: &str
+pub fn dest_path_owner(conn: &Connection, dest_path: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM files WHERE dest_path = ?1",
+        params![dest_path],
+        |row| row.get(0),
+    ).optional()
+}
+
+/// Model and taxonomy behind an AI classification.
+pub struct ClassifiedBy<'a> {
+    pub model: Option<&'a str>,
+    pub model_version: Option<&'a str>,
+    pub taxonomy_version: &'a str,
+}
+
+/// Sets the category of a file and who decided it; `classified_by` is `None` for anything the AI
+/// did not answer, which clears a previous model identity.
+pub fn update_classification(
+    conn: &Connection,
+    id: i64,
+    category: Option<&str>,
+    confidence: Option<f32>,
+    category_source: Option<&str>,
+    classification_error: Option<&str>,
+    classified_by: Option<&ClassifiedBy>
 ) -> Result<()> {
     conn.execute(
-        "UPDATE files SET 
-            category = ?1, 
-            confidence = ?2, 
-            date_source = ?3, 
-            date_value = ?4, 
-            artist = ?5, 
-            album = ?6,
-            dest_path = ?7
+        "UPDATE files SET
+            category = ?1,
+            confidence = ?2,
+            category_source = ?3,
+            classification_error = ?4,
+            model_name = ?5,
+            model_version = ?6,
+            taxonomy_version = ?7
          WHERE id = ?8",
-        params![category, confidence, date_source, date_value, artist, album, dest_path, id],
+        params![
+            category,
+            confidence,
+            category_source,
+            classification_error,
+            classified_by.and_then(|c| c.model),
+            classified_by.and_then(|c| c.model_version),
+            classified_by.map(|c| c.taxonomy_version),
+            id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Changes only the category shown for a file, keeping who classified it.
+pub fn update_category(conn: &Connection, id: i64, category: &str, confidence: f32) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET category = ?1, confidence = ?2 WHERE id = ?3",
+        params![category, confidence, id],
+    )?;
+    Ok(())
+}
+
+/// Replaces the stored scores of a file, ranking them from the highest score down.
+pub fn replace_file_tags(conn: &Connection, file_id: i64, scores: &[(&str, f32)]) -> Result<()> {
+    let mut ranked = scores.to_vec();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM file_tags WHERE file_id = ?1", params![file_id])?;
+    for (rank, (category, score)) in ranked.iter().enumerate() {
+        tx.execute(
+            "INSERT OR REPLACE INTO file_tags (file_id, category, score, rank) VALUES (?1, ?2, ?3, ?4)",
+            params![file_id, category, score, rank as i64 + 1],
+        )?;
+    }
+    tx.commit()
+}
+
+pub fn get_top_tag(conn: &Connection, file_id: i64) -> Result<Option<(String, f32)>> {
+    conn.query_row(
+        "SELECT category, score FROM file_tags WHERE file_id = ?1 ORDER BY rank LIMIT 1",
+        params![file_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()
+}
+
+pub fn update_metadata(
+    conn: &Connection,
+    id: i64,
+    date_source: Option<&str>,
+    date_value: Option<&str>,
+    artist: Option<&str>,
+    album: Option<&str>
+) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET date_source = ?1, date_value = ?2, artist = ?3, album = ?4 WHERE id = ?5",
+        params![date_source, date_value, artist, album, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_dest_path(conn: &Connection, id: i64, dest_path: &str, sanitized_components: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET dest_path = ?1, sanitized_components = ?2 WHERE id = ?3",
+        params![dest_path, sanitized_components, id],
     )?;
     Ok(())
 }
 
+/// Id and destination of every file Phase 3 projected.
+pub fn get_dest_paths(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare("SELECT id, dest_path FROM files WHERE dest_path IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Moves a file to a different name at staging time, keeping what Phase 3 recorded about it.
+pub fn rename_dest_path(conn: &Connection, id: i64, dest_path: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET dest_path = ?1 WHERE id = ?2",
+        params![dest_path, id],
+    )?;
+    Ok(())
+}
+
+pub fn get_images_without_phash(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path FROM files
+         WHERE status = 'PRIMARIO' AND mime_type LIKE 'image/%' AND phash IS NULL"
+    )?;
+    let files = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+pub fn set_phash(conn: &Connection, id: i64, phash: &str, width: u32, height: u32) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET phash = ?1, image_width = ?2, image_height = ?3 WHERE id = ?4",
+        params![phash, width, height, id],
+    )?;
+    Ok(())
+}
+
+pub struct ImageFingerprint {
+    pub id: i64,
+    pub phash: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: u64,
+}
+
+pub fn get_primary_fingerprints(conn: &Connection) -> Result<Vec<ImageFingerprint>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, phash, image_width, image_height, file_size FROM files
+         WHERE status = 'PRIMARIO' AND phash IS NOT NULL"
+    )?;
+    let files = stmt.query_map([], |row| {
+        Ok(ImageFingerprint {
+            id: row.get(0)?,
+            phash: row.get(1)?,
+            width: row.get(2)?,
+            height: row.get(3)?,
+            file_size: row.get(4)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
 pub struct StagingFile {
     pub id: i64,
     pub source_path: String,
     pub dest_path: Option<String>,
+    pub blake3_hash: Option<String>,
 }
 
 pub fn get_staging_files(conn: &Connection) -> Result<Vec<StagingFile>> {
-    let mut stmt = conn.prepare("SELECT id, source_path, dest_path FROM files WHERE status = 'PRIMARIO'")?;
+    // Copies that failed verification are tried again
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path, dest_path, blake3_hash FROM files
+         WHERE status IN ('PRIMARIO', 'ERROR_VERIFICACION')"
+    )?;
     let files = stmt.query_map([], |row| {
         Ok(StagingFile {
             id: row.get(0)?,
             source_path: row.get(1)?,
             dest_path: row.get(2)?,
+            blake3_hash: row.get(3)?,
         })
     })?.collect::<Result<Vec<_>>>()?;
     Ok(files)
@@ -126,3 +488,156 @@ pub fn update_staging_status(conn: &Connection, id: i64, status: &str, error_msg
     Ok(())
 }
 
+/// A file Phase 4 copied, as `rollback` needs it to tell its own copies from anything else.
+pub struct CompletedCopy {
+    pub id: i64,
+    pub source_path: String,
+    pub dest_path: String,
+    pub blake3_hash: Option<String>,
+    pub classification_error: Option<String>,
+}
+
+pub fn get_completed_copies(conn: &Connection) -> Result<Vec<CompletedCopy>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path, dest_path, blake3_hash, classification_error FROM files
+         WHERE status = 'COMPLETADO' AND dest_path IS NOT NULL
+         ORDER BY dest_path"
+    )?;
+    let files = stmt.query_map([], |row| {
+        Ok(CompletedCopy {
+            id: row.get(0)?,
+            source_path: row.get(1)?,
+            dest_path: row.get(2)?,
+            blake3_hash: row.get(3)?,
+            classification_error: row.get(4)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// A file `commit --per-file` may move: a staged primary, or a duplicate of one, together with
+/// what is needed to check the primary's copy.
+pub struct CommitCandidate {
+    pub id: i64,
+    pub source_path: String,
+    pub primary_id: i64,
+    pub primary_source: String,
+    pub primary_hash: Option<String>,
+    pub dest_path: String,
+}
+
+pub fn get_commit_candidates(conn: &Connection) -> Result<Vec<CommitCandidate>> {
+    // Only exact duplicates have the primary's bytes: a visual duplicate's content is not in the destination
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.source_path, p.id, p.source_path, p.blake3_hash, p.dest_path
+         FROM files f JOIN files p ON p.id = COALESCE(f.primary_id, f.id)
+         WHERE p.status = 'COMPLETADO' AND p.dest_path IS NOT NULL
+           AND (f.id = p.id OR f.status = 'DUPLICADO_EXACTO')
+         ORDER BY p.id, f.id"
+    )?;
+    let files = stmt.query_map([], |row| {
+        Ok(CommitCandidate {
+            id: row.get(0)?,
+            source_path: row.get(1)?,
+            primary_id: row.get(2)?,
+            primary_source: row.get(3)?,
+            primary_hash: row.get(4)?,
+            dest_path: row.get(5)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// Source path → status of every scanned file.
+pub fn get_statuses_by_path(conn: &Connection) -> Result<std::collections::HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT source_path, status FROM files")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn insert_commit_manifest(conn: &Connection, file_id: Option<i64>, source_path: &str, trash_path: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO commit_manifest (file_id, source_path, trash_path) VALUES (?1, ?2, ?3)",
+        params![file_id, source_path, trash_path],
+    )?;
+    Ok(())
+}
+
+pub struct ManifestEntry {
+    pub id: i64,
+    pub source_path: String,
+    pub trash_path: String,
+}
+
+/// Entries not restored yet, latest first, so nested moves are undone in reverse.
+pub fn get_unrestored_manifest(conn: &Connection) -> Result<Vec<ManifestEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_path, trash_path FROM commit_manifest WHERE restored_at IS NULL ORDER BY id DESC"
+    )?;
+    let entries = stmt.query_map([], |row| {
+        Ok(ManifestEntry {
+            id: row.get(0)?,
+            source_path: row.get(1)?,
+            trash_path: row.get(2)?,
+        })
+    })?.collect::<Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+pub fn mark_restored(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("UPDATE commit_manifest SET restored_at = CURRENT_TIMESTAMP WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+pub struct CachedClassification {
+    pub category: String,
+    pub confidence: f32,
+    /// JSON list of `{category, score}`
+    pub scores: Option<String>,
+}
+
+pub fn get_cached_classification(
+    cache: &Connection,
+    hash: &str,
+    model: &str,
+    model_version: &str,
+    taxonomy_version: &str
+) -> Result<Option<CachedClassification>> {
+    cache.query_row(
+        "SELECT category, confidence, scores FROM classification_cache
+         WHERE blake3_hash = ?1 AND model = ?2 AND model_version = ?3 AND taxonomy_version = ?4",
+        params![hash, model, model_version, taxonomy_version],
+        |row| Ok(CachedClassification {
+            category: row.get(0)?,
+            confidence: row.get(1)?,
+            scores: row.get(2)?,
+        }),
+    ).optional()
+}
+
+pub fn put_cached_classification(
+    cache: &Connection,
+    hash: &str,
+    model: &str,
+    model_version: &str,
+    taxonomy_version: &str,
+    entry: &CachedClassification
+) -> Result<()> {
+    cache.execute(
+        "INSERT OR REPLACE INTO classification_cache
+            (blake3_hash, model, model_version, taxonomy_version, category, confidence, scores)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![hash, model, model_version, taxonomy_version, entry.category, entry.confidence, entry.scores],
+    )?;
+    Ok(())
+}
+
+/// Used on both `state.db` and the classification cache, which each have a `meta` table.
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT value FROM meta WHERE key = ?1", params![key], |row| row.get(0)).optional()
+}
+
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)", params![key, value])?;
+    Ok(())
+}
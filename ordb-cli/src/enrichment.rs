@@ -2,156 +2,228 @@
 //! This module is part of the `ordb-cli` application.
 //! It provides the `project_dest_path` function to determine the final destination path
 //! of a file based on its metadata, including MIME type, dates, and category.
-//! It handles name collisions by injecting a hash prefix into the file name.
+//! Name collisions are resolved by the caller against the database and the destination
+//! filesystem; `with_name_suffix` injects the hash prefix used to disambiguate them.
+//! Every value is sanitized before it becomes a path component; originals that had to change
+//! are returned so they can be recorded.
 //!
 //! ES:
 //! Este módulo forma parte de la aplicación `ordb-cli`.
 //! Proporciona la función `project_dest_path` para determinar la ruta de destino final
 //! de un archivo en función de sus metadatos, incluyendo tipo MIME, fechas y categoría.
-//! Maneja las colisiones de nombres inyectando un prefijo del hash en el nombre del archivo.
+//! Las colisiones de nombres las resuelve quien llama contra la base de datos y el sistema de
+//! archivos de destino; `with_name_suffix` inyecta el prefijo del hash usado para distinguirlas.
+//! Cada valor se sanea antes de convertirse en componente de ruta; los originales que tuvieron
+//! que cambiar se devuelven para poder registrarlos.
 
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use chrono::{DateTime, Datelike};
-use std::collections::HashMap;
-
-pub fn project_dest_path(
-    base_dest: &Path,
-    source_path_str: &str,
-    mime: Option<&str>,
-    category: &str,
-    date_val: Option<&str>,
-    date_src: Option<&str>,
-    artist: Option<&str>,
-    album: Option<&str>,
-    hash: &str,
-    used_paths: &mut HashMap<String, String>,
-) -> String {
-    let source_path = Path::new(source_path_str);
-    let original_name = source_path.file_name().unwrap_or_default().to_string_lossy();
-    let mut relative_parts = Vec::new();
-
-    let is_image = mime.unwrap_or("").starts_with("image/");
-    let is_video = mime.unwrap_or("").starts_with("video/");
-    let is_audio = mime.unwrap_or("").starts_with("audio/");
-
-    if is_image || is_video {
-        relative_parts.push("Imagenes".to_string());
-        
-        if let Some(src) = date_src {
-            if src == "SOSPECHOSA" {
-                relative_parts.push("Fecha_Sospechosa".to_string());
-            } else if let Some(val) = date_val {
-                if let Ok(dt) = DateTime::parse_from_rfc3339(val) {
-                    relative_parts.push(format!("{:04}", dt.year()));
-                    let month_name = match dt.month() {
-                        1 => "Enero", 2 => "Febrero", 3 => "Marzo", 4 => "Abril",
-                        5 => "Mayo", 6 => "Junio", 7 => "Julio", 8 => "Agosto",
-                        9 => "Septiembre", 10 => "Octubre", 11 => "Noviembre", 12 => "Diciembre",
-                        _ => "Desconocido",
-                    };
-                    relative_parts.push(format!("{:02}_{}", dt.month(), month_name));
-                } else {
-                    relative_parts.push("Sin_Fecha".to_string());
-                }
-            } else {
-                relative_parts.push("Sin_Fecha".to_string());
-            }
-        } else {
-            relative_parts.push("Sin_Fecha".to_string());
-        }
-        
-        relative_parts.push(category.to_string());
-    } else if is_audio {
-        relative_parts.push("Musica".to_string());
-        if let Some(art) = artist {
-            relative_parts.push(art.to_string());
-            if let Some(alb) = album {
-                relative_parts.push(alb.to_string());
-            }
-        } else {
-            relative_parts.push("Desconocido".to_string());
-        }
+use crate::config::Layout;
+use crate::i18n::Catalog;
+use crate::sanitize::{self, sanitize_component};
+
+/// Everything Phase 3 knows about a primary file, exposed to the layout templates.
+pub struct FileMeta<'a> {
+    pub source_path: &'a str,
+    pub mime: Option<&'a str>,
+    pub category: &'a str,
+    pub confidence: Option<f32>,
+    pub date_val: Option<&'a str>,
+    pub date_src: Option<&'a str>,
+    pub artist: Option<&'a str>,
+    pub album: Option<&'a str>,
+    pub hash: Option<&'a str>,
+}
+
+pub struct Projection {
+    pub dest_path: String,
+    /// Template variable → original value, for every value the sanitizer had to change.
+    pub sanitized: BTreeMap<String, String>,
+}
+
+pub fn project_dest_path(base_dest: &Path, layout: &Layout, meta: &FileMeta) -> Projection {
+    let mime = meta.mime.unwrap_or("");
+    let catalog = layout.catalog;
+    let (template, media) = if mime.starts_with("image/") {
+        (&layout.image, catalog.media_images)
+    } else if mime.starts_with("video/") {
+        (&layout.video, catalog.media_images)
+    } else if mime.starts_with("audio/") {
+        (&layout.audio, catalog.media_music)
     } else {
-        relative_parts.push("Otros".to_string());
-        let ext = source_path.extension().unwrap_or_default().to_string_lossy();
-        if !ext.is_empty() {
-            relative_parts.push(ext.to_string());
-        } else {
-            relative_parts.push("Sin_Extension".to_string());
+        (&layout.other, catalog.media_other)
+    };
+
+    let mut vars = template_vars(meta, catalog);
+    vars.insert("media", media.to_string());
+
+    let mut sanitized = BTreeMap::new();
+    for (name, value) in vars.iter_mut() {
+        let safe = sanitize_component(value);
+        if safe != *value {
+            if template.uses(name) {
+                sanitized.insert(name.to_string(), value.clone());
+            }
+            *value = safe;
         }
     }
 
+    // Literals from the template and the combined length still need checking per segment
     let mut projected = base_dest.to_path_buf();
-    for part in relative_parts {
-        projected.push(part);
+    for part in template.render(&vars) {
+        projected.push(sanitize_component(&part));
     }
-    
-    let mut projected_file = projected.clone();
-    projected_file.push(original_name.as_ref());
-    
-    let mut final_path_str = projected_file.to_string_lossy().to_string();
-    
-    if let Some(existing_hash) = used_paths.get(&final_path_str) {
-        if existing_hash != hash {
-            let file_stem = source_path.file_stem().unwrap_or_default().to_string_lossy();
-            let ext = source_path.extension().unwrap_or_default().to_string_lossy();
-            let hash_prefix = &hash[0..8];
-            
-            let new_name = if ext.is_empty() {
-                format!("{}_{}", file_stem, hash_prefix)
-            } else {
-                format!("{}_{}.{}", file_stem, hash_prefix, ext)
-            };
-            
-            let mut projected_with_hash = projected;
-            projected_with_hash.push(new_name);
-            final_path_str = projected_with_hash.to_string_lossy().to_string();
+    Projection {
+        dest_path: projected.to_string_lossy().to_string(),
+        sanitized,
+    }
+}
+
+fn template_vars<'a>(meta: &FileMeta, catalog: &Catalog) -> HashMap<&'a str, String> {
+    let source_path = Path::new(meta.source_path);
+    let mut vars = HashMap::new();
+    let mut set = |name, value: Option<String>| {
+        if let Some(value) = value {
+            vars.insert(name, value);
         }
+    };
+
+    set("category", Some(meta.category.to_string()));
+    set("confidence", meta.confidence.map(|c| format!("{:.2}", c)));
+    set("date_source", meta.date_src.map(str::to_string));
+    set("artist", meta.artist.map(str::to_string));
+    set("album", meta.album.map(str::to_string));
+    set("filename", Some(source_path.file_name().unwrap_or_default().to_string_lossy().to_string()));
+    set("stem", Some(source_path.file_stem().unwrap_or_default().to_string_lossy().to_string()));
+    set("ext", source_path.extension().map(|e| e.to_string_lossy().to_string()));
+    set("mime", meta.mime.map(str::to_string));
+    set("hash", meta.hash.map(|h| h[..h.len().min(8)].to_string()));
+
+    let date = meta.date_val.and_then(|val| DateTime::parse_from_rfc3339(val).ok());
+    match (meta.date_src, date) {
+        (Some("SOSPECHOSA"), _) => set("date_status", Some(catalog.suspicious_date.to_string())),
+        (Some(_), Some(dt)) => {
+            set("year", Some(format!("{:04}", dt.year())));
+            set("month", Some(dt.month().to_string()));
+            set("day", Some(dt.day().to_string()));
+            set("month_name", Some(catalog.month(dt.month()).to_string()));
+        }
+        _ => set("date_status", Some(catalog.no_date.to_string())),
     }
-    
-    used_paths.insert(final_path_str.clone(), hash.to_string());
-    final_path_str
+    vars
+}
+
+/// Inserts `_suffix` between the file stem and its extension: `IMG_001.jpg` → `IMG_001_1a2b3c4d.jpg`.
+pub fn with_name_suffix(path_str: &str, suffix: &str) -> String {
+    let path = Path::new(path_str);
+    let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+
+    // Shorten the stem, not the suffix, when the name is already at the length limit
+    let tail = if ext.is_empty() { format!("_{}", suffix) } else { format!("_{}.{}", suffix, ext) };
+    let budget = sanitize::MAX_COMPONENT_BYTES.saturating_sub(tail.len());
+    let new_name = format!("{}{}", sanitize::truncate_bytes(&file_stem, budget), tail);
+    path.with_file_name(new_name).to_string_lossy().to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use crate::config::Config;
 
     #[test]
     fn test_name_collision_hash_injection() {
         let base_dest = Path::new("/dest");
-        let mut used_paths = HashMap::new();
-
-        let path1 = project_dest_path(
-            base_dest,
-            "/source1/IMG_001.jpg",
-            Some("image/jpeg"),
-            "Persona_Sola",
-            Some("2023-01-01T12:00:00Z"),
-            Some("EXIF_ORIGINAL"),
-            None,
-            None,
-            "1111111111111111111111111111111111111111111111111111111111111111",
-            &mut used_paths,
-        );
+        let layout = Config::default().layout(&crate::i18n::ES).unwrap();
+        let meta = |source_path, date_val| FileMeta {
+            source_path,
+            mime: Some("image/jpeg"),
+            category: "Persona_Sola",
+            confidence: Some(0.9),
+            date_val: Some(date_val),
+            date_src: Some("EXIF_ORIGINAL"),
+            artist: None,
+            album: None,
+            hash: None,
+        };
 
-        let path2 = project_dest_path(
-            base_dest,
-            "/source2/IMG_001.jpg",
-            Some("image/jpeg"),
-            "Persona_Sola",
-            Some("2023-01-01T15:00:00Z"),
-            Some("EXIF_ORIGINAL"),
-            None,
-            None,
-            "2222222222222222222222222222222222222222222222222222222222222222",
-            &mut used_paths,
-        );
+        let path1 = project_dest_path(base_dest, &layout, &meta("/source1/IMG_001.jpg", "2023-01-01T12:00:00Z")).dest_path;
+        let path2 = project_dest_path(base_dest, &layout, &meta("/source2/IMG_001.jpg", "2023-01-01T15:00:00Z")).dest_path;
 
         #[cfg(target_os = "windows")]
         assert!(path1.ends_with("IMG_001.jpg") || path1.ends_with("IMG_001.jpg"));
-        
-        assert!(path2.ends_with("IMG_001_22222222.jpg"));
+
+        assert_eq!(path1, path2);
+        assert_eq!(
+            Path::new(&path1),
+            Path::new("/dest/Imagenes/2023/01_Enero/Persona_Sola/IMG_001.jpg"),
+        );
+
+        let english = Config::default().layout(&crate::i18n::EN).unwrap();
+        let path3 = project_dest_path(base_dest, &english, &meta("/source3/IMG_001.jpg", "2023-01-01T12:00:00Z")).dest_path;
+        assert_eq!(
+            Path::new(&path3),
+            Path::new("/dest/Images/2023/01_January/Persona_Sola/IMG_001.jpg"),
+        );
+
+        let song = FileMeta {
+            source_path: "/music/track01.mp3",
+            mime: Some("audio/mpeg"),
+            category: "Musica",
+            artist: Some("AC/DC"),
+            album: Some("Live?"),
+            ..meta("", "")
+        };
+        let projection = project_dest_path(base_dest, &layout, &song);
+        assert_eq!(Path::new(&projection.dest_path), Path::new("/dest/Musica/AC_DC/Live_/track01.mp3"));
+        assert_eq!(projection.sanitized.get("artist").map(String::as_str), Some("AC/DC"));
+        assert_eq!(projection.sanitized.get("album").map(String::as_str), Some("Live?"));
+
+        let hash = "2222222222222222222222222222222222222222222222222222222222222222";
+        assert!(with_name_suffix(&path2, &hash[0..8]).ends_with("IMG_001_22222222.jpg"));
+        assert!(with_name_suffix("/dest/Otros/Sin_Extension/LEEME", "1").ends_with("LEEME_1"));
+    }
+
+    #[test]
+    fn test_default_layout_matches_baseline() {
+        let layout = Config::default().layout(&crate::i18n::ES).unwrap();
+        let project = |source_path, mime, date_src, date_val, artist, album| {
+            let meta = FileMeta {
+                source_path,
+                mime: Some(mime),
+                category: "Persona_Sola",
+                confidence: None,
+                date_val,
+                date_src,
+                artist,
+                album,
+                hash: None,
+            };
+            project_dest_path(Path::new("/dest"), &layout, &meta).dest_path
+        };
+        let cases = [
+            (project("/s/a.jpg", "image/jpeg", Some("EXIF_ORIGINAL"), Some("2023-03-05T10:00:00Z"), None, None),
+                "/dest/Imagenes/2023/03_Marzo/Persona_Sola/a.jpg"),
+            (project("/s/a.jpg", "image/jpeg", Some("SOSPECHOSA"), Some("1970-01-01T00:00:00Z"), None, None),
+                "/dest/Imagenes/Fecha_Sospechosa/Persona_Sola/a.jpg"),
+            (project("/s/a.jpg", "image/jpeg", Some("NINGUNA"), None, None, None),
+                "/dest/Imagenes/Sin_Fecha/Persona_Sola/a.jpg"),
+            (project("/s/a.mp4", "video/mp4", Some("FILESYSTEM"), Some("2021-12-31T23:00:00Z"), None, None),
+                "/dest/Imagenes/2021/12_Diciembre/Persona_Sola/a.mp4"),
+            (project("/s/t.mp3", "audio/mpeg", None, None, Some("Artista"), Some("Disco")),
+                "/dest/Musica/Artista/Disco/t.mp3"),
+            (project("/s/t.mp3", "audio/mpeg", None, None, Some("Artista"), None),
+                "/dest/Musica/Artista/t.mp3"),
+            (project("/s/t.mp3", "audio/mpeg", None, None, None, None),
+                "/dest/Musica/Desconocido/t.mp3"),
+            (project("/s/doc.pdf", "application/pdf", None, None, None, None),
+                "/dest/Otros/pdf/doc.pdf"),
+            (project("/s/LEEME", "text/plain", None, None, None, None),
+                "/dest/Otros/Sin_Extension/LEEME"),
+        ];
+        for (projected, baseline) in cases {
+            assert_eq!(Path::new(&projected), Path::new(baseline));
+        }
     }
 }
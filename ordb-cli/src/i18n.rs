@@ -0,0 +1,254 @@
+//! EN:
+//! Message catalogs. The `--locale` option picks the catalog that names destination folders
+//! (media families, months, missing-date buckets) and prints the CLI progress messages.
+//! Messages with parameters use `{}` placeholders filled in order by `fill`.
+//!
+//! ES:
+//! Catálogos de mensajes. La opción `--locale` elige el catálogo que nombra las carpetas de
+//! destino (familias de medios, meses, carpetas sin fecha) y los mensajes de progreso de la CLI.
+//! Los mensajes con parámetros usan marcadores `{}` que `fill` completa en orden.
+
+use std::fmt::Display;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Locale {
+    /// Español
+    Es,
+    /// English
+    En,
+}
+
+impl Locale {
+    pub fn catalog(self) -> &'static Catalog {
+        match self {
+            Locale::Es => &ES,
+            Locale::En => &EN,
+        }
+    }
+}
+
+pub struct Catalog {
+    // Destination folder names
+    pub months: [&'static str; 12],
+    pub media_images: &'static str,
+    pub media_music: &'static str,
+    pub media_other: &'static str,
+    pub no_date: &'static str,
+    pub suspicious_date: &'static str,
+    pub unknown: &'static str,
+    pub no_extension: &'static str,
+
+    // CLI messages
+    pub starting: &'static str,
+    pub db_initialized: &'static str,
+    pub sources_required: &'static str,
+    pub phase1: &'static str,
+    pub ai_ready: &'static str,
+    pub ai_disabled: &'static str,
+    pub ai_unavailable: &'static str,
+    pub phase2: &'static str,
+    pub primary_selection: &'static str,
+    pub phase2_done: &'static str,
+    pub phase2b: &'static str,
+    pub phase2b_done: &'static str,
+    pub phase3: &'static str,
+    pub cache_hits: &'static str,
+    pub reclassify_stale: &'static str,
+    pub classify_failed: &'static str,
+    pub reclassify_failed: &'static str,
+    pub phase3_done: &'static str,
+    pub phase4: &'static str,
+    pub dry_run_skip: &'static str,
+    pub report_source: &'static str,
+    pub report_dest: &'static str,
+    pub report_total: &'static str,
+    pub report_exported: &'static str,
+    pub temp_files_removed: &'static str,
+    pub phase4_done: &'static str,
+    pub final_report: &'static str,
+    pub no_sources: &'static str,
+    pub source_missing: &'static str,
+    pub moving: &'static str,
+    pub rename_fallback: &'static str,
+    pub commit_done: &'static str,
+    pub commit_moved: &'static str,
+    pub left_behind: &'static str,
+    pub left_unverified: &'static str,
+    pub left_unscanned: &'static str,
+    pub nothing_to_rollback: &'static str,
+    pub rollback_list: &'static str,
+    pub rollback_confirm: &'static str,
+    pub yes: &'static str,
+    pub rollback_cancelled: &'static str,
+    pub rollback_kept: &'static str,
+    pub kept_modified: &'static str,
+    pub kept_source_missing: &'static str,
+    pub rollback_done: &'static str,
+    pub nothing_to_restore: &'static str,
+    pub restore_missing: &'static str,
+    pub restore_conflict: &'static str,
+    pub restore_done: &'static str,
+    pub purge_needs_force: &'static str,
+    pub purging: &'static str,
+    pub purge_done: &'static str,
+}
+
+impl Catalog {
+    pub fn month(&self, month: u32) -> &'static str {
+        match month {
+            1..=12 => self.months[month as usize - 1],
+            _ => self.unknown,
+        }
+    }
+}
+
+/// Replaces each `{}` in `template` with the next argument.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub static ES: Catalog = Catalog {
+    months: [
+        "Enero", "Febrero", "Marzo", "Abril", "Mayo", "Junio",
+        "Julio", "Agosto", "Septiembre", "Octubre", "Noviembre", "Diciembre",
+    ],
+    media_images: "Imagenes",
+    media_music: "Musica",
+    media_other: "Otros",
+    no_date: "Sin_Fecha",
+    suspicious_date: "Fecha_Sospechosa",
+    unknown: "Desconocido",
+    no_extension: "Sin_Extension",
+
+    starting: "Iniciando la organización de archivos...",
+    db_initialized: "Base de datos inicializada en {}.",
+    sources_required: "Se requieren las opciones de origen y destino para escanear.",
+    phase1: "Fase 1: Preparación",
+    ai_ready: "El microservicio de IA está listo.",
+    ai_disabled: "IA desactivada (--no-ai o backend = \"rules\"): las imágenes se clasificarán por reglas.",
+    ai_unavailable: "Advertencia: microservicio de IA no disponible ({}). Las imágenes se clasificarán por reglas.",
+    phase2: "Fase 2: Escaneo y deduplicación",
+    primary_selection: "Selección de primarios: {} grupos de duplicados cambiaron de primario.",
+    phase2_done: "Fase 2 completada.",
+    phase2b: "Fase 2b: Deduplicación visual",
+    phase2b_done: "Fase 2b completada.",
+    phase3: "Fase 3: Enriquecimiento y clasificación",
+    cache_hits: "{} imágenes clasificadas desde la caché.",
+    reclassify_stale: "{} imágenes se clasificaron con otro modelo o taxonomía y se volverán a clasificar.",
+    classify_failed: "Advertencia: no se pudieron clasificar {} imágenes. Se copiarán como desconocidas; ejecute de nuevo con --resume para reclasificarlas.",
+    reclassify_failed: "{} imágenes que no se pudieron clasificar antes se volverán a clasificar.",
+    phase3_done: "Fase 3 completada.",
+    phase4: "Fase 4: Preparación de copias",
+    dry_run_skip: "Simulación activada. No se copiarán archivos.",
+    report_source: "ORIGEN",
+    report_dest: "DESTINO",
+    report_total: "Total archivos proyectados: {}",
+    report_exported: "Reporte exportado a: {}",
+    temp_files_removed: "Se eliminaron {} copias temporales de una ejecución interrumpida.",
+    phase4_done: "Fase 4 completada.",
+    final_report: "=== Reporte Final ===",
+    no_sources: "No hay directorios de origen registrados. Ejecute primero el proceso.",
+    source_missing: "El origen {} ya no existe, se omite.",
+    moving: "Moviendo {} a {}",
+    rename_fallback: "Falló el renombrado (¿otro dispositivo?), se copia y luego se borra...",
+    commit_done: "Confirmación completada.",
+    commit_moved: "{} archivos movidos a la papelera.",
+    left_behind: "{} archivos se quedaron en su sitio:",
+    left_unverified: "copia sin verificar",
+    left_unscanned: "no escaneado",
+    nothing_to_rollback: "No hay copias que revertir.",
+    rollback_list: "Se eliminarán {} archivos copiados por ordb y las carpetas que queden vacías.",
+    rollback_confirm: "¿Continuar con el rollback? (s/n): ",
+    yes: "s",
+    rollback_cancelled: "Rollback cancelado.",
+    rollback_kept: "Se conserva {} ({}).",
+    kept_modified: "modificado desde la copia",
+    kept_source_missing: "el original ya no está en su sitio",
+    rollback_done: "Rollback completado.",
+    nothing_to_restore: "No hay nada que restaurar.",
+    restore_missing: "{} ya no está en la papelera, se omite.",
+    restore_conflict: "Conflicto: {} ya existe de nuevo; {} se queda en la papelera.",
+    restore_done: "Restauración completada: {} restaurados, {} conflictos.",
+    purge_needs_force: "La purga requiere la opción --force.",
+    purging: "Purgando papelera: {}",
+    purge_done: "Purga completada.",
+};
+
+pub static EN: Catalog = Catalog {
+    months: [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ],
+    media_images: "Images",
+    media_music: "Music",
+    media_other: "Other",
+    no_date: "No_Date",
+    suspicious_date: "Suspicious_Date",
+    unknown: "Unknown",
+    no_extension: "No_Extension",
+
+    starting: "Starting file organization...",
+    db_initialized: "Database initialized at {}.",
+    sources_required: "Source and destination flags are required for scanning.",
+    phase1: "Phase 1: Warm-up",
+    ai_ready: "AI Microservice is ready.",
+    ai_disabled: "AI disabled (--no-ai or backend = \"rules\"): images will be classified by rules.",
+    ai_unavailable: "Warning: AI microservice unavailable ({}). Images will be classified by rules.",
+    phase2: "Phase 2: Scanning & Deduplication",
+    primary_selection: "Primary selection: {} duplicate groups changed primary.",
+    phase2_done: "Phase 2 Complete.",
+    phase2b: "Phase 2b: Visual Deduplication",
+    phase2b_done: "Phase 2b Complete.",
+    phase3: "Phase 3: Enrichment & Classification",
+    cache_hits: "{} images classified from the cache.",
+    reclassify_stale: "{} images were classified by another model or taxonomy and will be classified again.",
+    classify_failed: "Warning: {} images could not be classified. They will be copied as unknown; run again with --resume to reclassify them.",
+    reclassify_failed: "{} images that could not be classified before will be classified again.",
+    phase3_done: "Phase 3 Complete.",
+    phase4: "Phase 4: Staging",
+    dry_run_skip: "Dry run enabled. Skipping physical file copies.",
+    report_source: "SOURCE",
+    report_dest: "DESTINATION",
+    report_total: "Total projected files: {}",
+    report_exported: "Report exported to: {}",
+    temp_files_removed: "Removed {} temporary copies left by an interrupted run.",
+    phase4_done: "Phase 4 Complete.",
+    final_report: "=== Final Report ===",
+    no_sources: "No source directories recorded. Run the pipeline first.",
+    source_missing: "Source {} no longer exists, skipping.",
+    moving: "Moving {} to {}",
+    rename_fallback: "rename failed (cross-device?), falling back to copy + delete...",
+    commit_done: "Commit successfully completed.",
+    commit_moved: "{} files moved to the trash.",
+    left_behind: "{} files stayed in place:",
+    left_unverified: "copy not verified",
+    left_unscanned: "not scanned",
+    nothing_to_rollback: "No copies to roll back.",
+    rollback_list: "{} files copied by ordb will be deleted, along with the folders left empty.",
+    rollback_confirm: "Continue with the rollback? (y/n): ",
+    yes: "y",
+    rollback_cancelled: "Rollback cancelled.",
+    rollback_kept: "Keeping {} ({}).",
+    kept_modified: "modified since it was copied",
+    kept_source_missing: "the original is no longer in place",
+    rollback_done: "Rollback successfully completed.",
+    nothing_to_restore: "Nothing to restore.",
+    restore_missing: "{} is no longer in the trash, skipping.",
+    restore_conflict: "Conflict: {} exists again; {} stays in the trash.",
+    restore_done: "Restore completed: {} restored, {} conflicts.",
+    purge_needs_force: "Purge requires --force flag.",
+    purging: "Purging trash dir: {}",
+    purge_done: "Purge successfully completed.",
+};
@@ -1,11 +1,19 @@
 mod cli;
+mod config;
 mod db;
+mod i18n;
 
 mod api_client;
+mod classifier;
 mod phases;
 mod scanner;
 mod metadata;
 mod enrichment;
+mod template;
+mod perceptual;
+mod policy;
+mod rules;
+mod sanitize;
 
 use clap::Parser;
 use crate::cli::{Cli, Commands};
@@ -16,34 +24,41 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init();
     
     let args = Cli::parse();
+    let catalog = args.locale.catalog();
 
     match &args.command {
-        Some(Commands::Commit) => {
+        Some(Commands::Commit { per_file }) => {
             let db = db::init_db("state.db")?;
-            phases::commit(&db)?;
+            phases::commit(&db, *per_file, catalog)?;
         }
         Some(Commands::Rollback) => {
             let db = db::init_db("state.db")?;
-            phases::rollback(&db)?;
+            phases::rollback(&db, catalog)?;
+        }
+        Some(Commands::Restore) => {
+            let db = db::init_db("state.db")?;
+            phases::restore(&db, catalog)?;
         }
         Some(Commands::Purge { force }) => {
             let db = db::init_db("state.db")?;
-            phases::purge(&db, *force)?;
+            phases::purge(&db, *force, catalog)?;
         }
         None => {
             if args.source.is_empty() || args.destination.is_none() {
-                anyhow::bail!("Source and destination flags are required for scanning.");
+                anyhow::bail!(catalog.sources_required);
             }
-            println!("Starting file organization...");
+            let config = config::Config::load(args.config.as_deref())?;
+            println!("{}", catalog.starting);
             
             let db_path = "state.db";
-            if !args.resume && std::path::Path::new(db_path).exists() {
-                std::fs::remove_file(db_path)?;
-            }
             let db = db::init_db(db_path)?;
-            println!("Database initialized at {}.", db_path);
+            if !args.resume {
+                db::reset(&db)?;
+            }
+            println!("{}", i18n::fill(catalog.db_initialized, &[&db_path]));
             
-            phases::run_pipeline(&args, &db).await?;
+            let cache = db::init_cache_db("classification_cache.db")?;
+            phases::run_pipeline(&args, &config, &db, &cache).await?;
         }
     }
 
@@ -53,14 +53,15 @@ pub fn extract_date(path: &str) -> DateInfo {
 }
 
 pub fn extract_music_tags(path: &str) -> MusicInfo {
+    // Missing tags stay None; the layout falls back to the locale's "unknown" folder
     if let Ok(tag) = id3::Tag::read_from_path(path) {
         return MusicInfo {
-            artist: Some(tag.artist().unwrap_or("Desconocido").to_string()),
-            album: Some(tag.album().unwrap_or("Desconocido").to_string()),
+            artist: tag.artist().map(|s| s.to_string()),
+            album: tag.album().map(|s| s.to_string()),
         };
     }
     MusicInfo {
-        artist: Some("Desconocido".to_string()),
-        album: Some("Desconocido".to_string()),
+        artist: None,
+        album: None,
     }
 }
@@ -0,0 +1,176 @@
+//! EN:
+//! Perceptual fingerprints (dHash) for images. Two copies of the same photo that were resized,
+//! re-compressed or re-saved in another format have different bytes but nearly identical
+//! fingerprints, so they can be grouped by Hamming distance.
+//!
+//! ES:
+//! Huellas perceptuales (dHash) para imágenes. Dos copias de la misma foto redimensionadas,
+//! recomprimidas o guardadas en otro formato tienen bytes distintos pero huellas casi idénticas,
+//! por lo que pueden agruparse por distancia de Hamming.
+
+use std::collections::HashMap;
+use std::path::Path;
+use image::imageops::FilterType;
+
+pub struct Fingerprint {
+    pub hash: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A candidate for grouping. `quality` orders the members of a group: the highest one stays
+/// primary and the rest become its visual duplicates.
+pub struct Candidate {
+    pub id: i64,
+    pub hash: u64,
+    pub quality: (u64, u64),
+}
+
+/// Difference hash: shrink to 9x8 grayscale and record whether each pixel is brighter than its
+/// right-hand neighbour.
+pub fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    let img = image::open(path).ok()?;
+    let (width, height) = (img.width(), img.height());
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(Fingerprint { hash, width, height })
+}
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Greedy clustering: the best remaining candidate claims every unclaimed candidate within
+/// `threshold` of itself. Comparing against the leader only (instead of chaining neighbours)
+/// keeps a slow drift of edits from pulling unrelated images into one group.
+///
+/// Hashes within `threshold` of all-zeros or all-ones look like a flat image (blank frames,
+/// solid fills) and would match each other regardless of content, so they are never grouped.
+/// Returns `(leader, members)` for every group with at least one member.
+pub fn group_near_duplicates(mut candidates: Vec<Candidate>, threshold: u32) -> Vec<(i64, Vec<i64>)> {
+    candidates.retain(|c| {
+        let ones = c.hash.count_ones();
+        ones > threshold && ones < 64u32.saturating_sub(threshold)
+    });
+    candidates.sort_by(|a, b| b.quality.cmp(&a.quality).then(a.id.cmp(&b.id)));
+
+    let mut tree = BkTree::default();
+    for (idx, c) in candidates.iter().enumerate() {
+        tree.insert(c.hash, idx);
+    }
+
+    let mut claimed = vec![false; candidates.len()];
+    let mut groups = Vec::new();
+    for (idx, leader) in candidates.iter().enumerate() {
+        if claimed[idx] {
+            continue;
+        }
+        claimed[idx] = true;
+        let mut members = Vec::new();
+        for other in tree.find(leader.hash, threshold) {
+            if !claimed[other] {
+                claimed[other] = true;
+                members.push(candidates[other].id);
+            }
+        }
+        if !members.is_empty() {
+            members.sort();
+            groups.push((leader.id, members));
+        }
+    }
+    groups
+}
+
+/// Burkhard-Keller tree over Hamming distance, so radius queries avoid comparing every pair.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    items: Vec<usize>,
+    children: HashMap<u32, usize>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode { hash, items: vec![item], children: HashMap::new() });
+            return;
+        }
+        let mut current = 0;
+        loop {
+            let distance = hamming(self.nodes[current].hash, hash);
+            if distance == 0 {
+                self.nodes[current].items.push(item);
+                return;
+            }
+            match self.nodes[current].children.get(&distance) {
+                Some(&next) => current = next,
+                None => {
+                    let new_idx = self.nodes.len();
+                    self.nodes.push(BkNode { hash, items: vec![item], children: HashMap::new() });
+                    self.nodes[current].children.insert(distance, new_idx);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn find(&self, hash: u64, threshold: u32) -> Vec<usize> {
+        let mut found = Vec::new();
+        if self.nodes.is_empty() {
+            return found;
+        }
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let distance = hamming(node.hash, hash);
+            if distance <= threshold {
+                found.extend(&node.items);
+            }
+            let low = distance.saturating_sub(threshold);
+            let high = distance + threshold;
+            for (&d, &child) in &node.children {
+                if d >= low && d <= high {
+                    stack.push(child);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groups_by_leader_distance() {
+        let candidates = vec![
+            Candidate { id: 1, hash: 0xF0F0, quality: (100, 10) },
+            // Lower resolution re-save of 1
+            Candidate { id: 2, hash: 0xF0F1, quality: (50, 5) },
+            // Within range of 2 but not of the leader 1
+            Candidate { id: 3, hash: 0xF0F7, quality: (40, 4) },
+            Candidate { id: 4, hash: 0xFFFF_0000_0000, quality: (100, 10) },
+            // Flat images match each other but are never grouped
+            Candidate { id: 5, hash: 0, quality: (100, 10) },
+            Candidate { id: 6, hash: 0, quality: (10, 1) },
+        ];
+
+        let groups = group_near_duplicates(candidates, 2);
+
+        assert_eq!(groups, vec![(1, vec![2])]);
+    }
+}
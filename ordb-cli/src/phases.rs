@@ -1,31 +1,62 @@
 use crate::api_client;
+use crate::classifier::{self, Backend, Classifier};
 use crate::cli::Cli;
+use crate::config::Config;
 use crate::db;
 use crate::scanner;
 use crate::metadata;
 use crate::enrichment;
+use crate::i18n::{self, Catalog};
+use crate::perceptual;
+use crate::policy;
 use rusqlite::Connection;
-use reqwest::Client;
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
-    let client = Client::new();
-    let api_url = "http://127.0.0.1:8000"; // the default url for uvicorn
+pub async fn run_pipeline(args: &Cli, config: &Config, conn: &Connection, cache: &Connection) -> anyhow::Result<()> {
+    let catalog = args.locale.catalog();
+    let layout = config.layout(catalog)?;
+    let taxonomy = Arc::new(config.taxonomy());
+    // The rules answer whatever the chosen backend cannot
+    let backend = if args.no_ai { Backend::Rules } else { config.classifier.backend };
+    let fallback: Arc<dyn Classifier> = Arc::new(classifier::Rules::new(catalog.unknown));
+    let service: Option<Arc<dyn Classifier>> = match backend {
+        Backend::Http => {
+            let client = api_client::build_client(&service_options(args))?;
+            Some(Arc::new(classifier::Http::new(client, &args.ai_url)))
+        }
+        Backend::Mock => Some(Arc::new(classifier::Mock)),
+        Backend::Rules => None,
+    };
     
-    // Phase 1: Warm-up
-    println!("Phase 1: Warm-up");
-    api_client::warm_up(&client, api_url).await?;
-    println!("AI Microservice is ready.");
+    // Phase 1: Warm-up. It runs in the background while scanning and is only waited for once an
+    // image actually has to be sent to the service
+    println!("{}", catalog.phase1);
+    let mut warm_up = match &service {
+        Some(service) => {
+            let service = service.clone();
+            Some((service.clone(), tokio::spawn(async move { service.warm_up().await })))
+        }
+        None => {
+            println!("{}", catalog.ai_disabled);
+            None
+        }
+    };
     
     // Phase 2: Scanning & Deduplication
-    println!("Phase 2: Scanning & Deduplication");
+    println!("{}", catalog.phase2);
     // Store source directories for commit/purge
     for src in &args.source {
         let abs = src.canonicalize().unwrap_or(src.clone());
         db::insert_source(conn, &abs.to_string_lossy())?;
     }
-    let mut rx = scanner::scan_directories(args.source.clone()).await;
+    let scan_options = scanner::ScanOptions {
+        threads: scan_threads(args),
+        large_file_threshold: (args.large_file_mb > 0).then(|| args.large_file_mb * 1024 * 1024),
+    };
+    let mut rx = scanner::scan_directories(args.source.clone(), scan_options).await;
     while let Some(file_info) = rx.recv().await {
         // Insert as PENDIENTE (returns None if already exists from a previous run)
         let id = db::insert_file(
@@ -34,6 +65,8 @@ pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
             file_info.size,
             file_info.mime_type.as_deref(),
             file_info.hash.as_deref(),
+            file_info.partial_hash.as_deref(),
+            file_info.hash_level.as_str(),
         )?;
 
         let id = match id {
@@ -47,115 +80,211 @@ pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
             continue;
         }
 
-        let hash = file_info.hash.as_deref().unwrap_or("");
+        let hash = match resolve_full_hash(conn, id, &file_info) {
+            Ok(hash) => hash,
+            Err(e) => {
+                db::update_staging_status(conn, id, "OMITIDO", Some(&format!("Error al leer contenido: {}", e)))?;
+                continue;
+            }
+        };
 
-        // Deduplicate
-        if let Some(primary_id) = db::find_primary_by_hash(conn, hash)? {
-            db::update_status(conn, id, "DUPLICADO_EXACTO", Some(primary_id))?;
-        } else {
-            db::update_status(conn, id, "PRIMARIO", None)?;
+        // Deduplicate (a file without a full hash has no possible duplicate)
+        match hash {
+            Some(hash) => {
+                if let Some(primary_id) = db::find_primary_by_hash(conn, &hash)? {
+                    if args.paranoid {
+                        let (status, error_msg) = verify_duplicate(conn, &file_info.path, primary_id)?;
+                        db::update_status_with_error(conn, id, status, Some(primary_id), error_msg.as_deref())?;
+                    } else {
+                        db::update_status(conn, id, "DUPLICADO_EXACTO", Some(primary_id))?;
+                    }
+                } else {
+                    db::update_status(conn, id, "PRIMARIO", None)?;
+                }
+            }
+            None => db::update_status(conn, id, "PRIMARIO", None)?,
         }
     }
-    println!("Phase 2 Complete.");
+    let reelected = elect_primaries(args, conn)?;
+    println!("{}", i18n::fill(catalog.primary_selection, &[&reelected]));
+    println!("{}", catalog.phase2_done);
+
+    if args.visual_dedup {
+        println!("{}", catalog.phase2b);
+        visual_dedup(args, conn).await?;
+        println!("{}", catalog.phase2b_done);
+    }
     
     // Phase 3: Enrichment & Classification
-    println!("Phase 3: Enrichment & Classification");
+    println!("{}", catalog.phase3);
+    if args.resume && !args.dry_run {
+        // Before Phase 3 projects again, while dest_path is still where those were written
+        let removed = remove_stale_temp_files(conn)?;
+        if removed > 0 {
+            println!("{}", i18n::fill(catalog.temp_files_removed, &[&removed]));
+        }
+        let unstaged = unstage_failed_classifications(conn, args.destination.as_deref())?;
+        if unstaged > 0 {
+            println!("{}", i18n::fill(catalog.reclassify_failed, &[&unstaged]));
+        }
+    }
     let primary_files = db::get_primary_files(conn)?;
-    let mut used_paths = HashMap::new();
     let dest_base = args.destination.as_ref()
         .ok_or_else(|| anyhow::anyhow!("--destination is required for the pipeline"))?;
+    // Rollback prunes emptied folders up to here and no further
+    db::set_meta(conn, "destination", &dest_base.to_string_lossy())?;
 
-    let mut images_to_classify = Vec::new();
-    
-    for file in &primary_files {
-        let is_image = file.mime_type.as_deref().unwrap_or("").starts_with("image/");
-        if is_image {
-            images_to_classify.push(file.source_path.clone());
-        }
-    }
-    
-    // Process classification in batches
-    let mut classification_results = HashMap::new();
-    for chunk in images_to_classify.chunks(args.batch_size) {
-        if let Ok(results) = api_client::classify_batch(&client, api_url, chunk.to_vec()).await {
-            for res in results {
-                let cat = if res.confidence < args.confidence_threshold {
-                    "Desconocido".to_string()
-                } else {
-                    res.category
-                };
-                classification_results.insert(res.path, (cat, res.confidence));
+    // 3a. Classification runs in the background while metadata is extracted below. Until the
+    // service answers, the model it reported last time stands in for it, so images already
+    // classified or cached never wait for the warm-up.
+    let remembered = match db::get_meta(cache, "service")? {
+        Some(json) if service.is_some() => serde_json::from_str::<api_client::ServiceInfo>(&json).ok(),
+        _ => None,
+    };
+    let mut classifications = Classifications {
+        conn,
+        cache,
+        service: remembered.filter(|s| s.cacheable()),
+        source: service.as_ref().unwrap_or(&fallback).source(),
+        taxonomy_version: &taxonomy.version,
+        threshold: args.confidence_threshold,
+        catalog,
+        ids: HashMap::new(),
+    };
+    let mut plan = classifications.plan(args, &primary_files).await?;
+    let mut active = fallback.clone();
+    if let (false, Some((service, handle))) = (plan.pending.is_empty(), warm_up.take()) {
+        match handle.await? {
+            Ok(info) => {
+                println!("{}", catalog.ai_ready);
+                active = service;
+                db::set_meta(cache, "service", &serde_json::to_string(&info)?)?;
+                // Another model now answers: what was judged against the old one is judged again
+                let current = Some(info).filter(|s| s.cacheable());
+                if current != classifications.service {
+                    classifications.service = current;
+                    plan = classifications.plan(args, &primary_files).await?;
+                }
             }
-        } else {
-            eprintln!("Warning: Failed to classify a batch of images.");
+            Err(e) => eprintln!("{}", i18n::fill(catalog.ai_unavailable, &[&e])),
         }
     }
+    if let Some((_, handle)) = warm_up {
+        handle.abort();
+    }
+    classifications.source = active.source();
+    if classifications.source != "IA" {
+        // Nothing the rules answer belongs in the cache
+        classifications.service = None;
+    }
+    if plan.stale > 0 {
+        println!("{}", i18n::fill(catalog.reclassify_stale, &[&plan.stale]));
+    }
+    if plan.cache_hits > 0 {
+        println!("{}", i18n::fill(catalog.cache_hits, &[&plan.cache_hits]));
+    }
 
-    for file in primary_files {
-        let is_image = file.mime_type.as_deref().unwrap_or("").starts_with("image/");
-        let is_audio = file.mime_type.as_deref().unwrap_or("").starts_with("audio/");
+    let mut pending = Vec::new();
+    for (id, path, hash) in plan.pending {
+        pending.push(path.clone());
+        classifications.ids.insert(path, (id, hash));
+    }
+
+    let mut outcomes = classifier::spawn_classification(
+        active,
+        pending,
+        args.batch_size,
+        args.ai_concurrency,
+        api_client::BatchOptions {
+            upload: api_client::Upload { mode: args.ai_upload, thumbnail_size: args.thumbnail_size },
+            taxonomy: taxonomy.clone(),
+            retries: args.ai_retries,
+        },
+    );
+    let mut failed = 0;
 
-        let mut date_src = None;
-        let mut date_val = None;
-        let mut artist = None;
-        let mut album = None;
-        let mut category = "Otros".to_string();
-        let mut confidence = None;
+    // 3b. Metadata, and categories that need no classifier
+    for file in &primary_files {
+        let mime = file.mime_type.as_deref().unwrap_or("");
 
-        if is_image {
+        if mime.starts_with("image/") {
             let d_info = metadata::extract_date(&file.source_path);
-            date_src = Some(d_info.source);
-            date_val = d_info.value;
-            
-            if let Some((cat, conf)) = classification_results.get(&file.source_path) {
-                category = cat.clone();
-                confidence = Some(*conf);
-            } else {
-                category = "Desconocido".to_string();
-            }
-        } else if is_audio {
+            db::update_metadata(conn, file.id, Some(&d_info.source), d_info.value.as_deref(), None, None)?;
+        } else if mime.starts_with("audio/") {
             let m_info = metadata::extract_music_tags(&file.source_path);
-            artist = m_info.artist;
-            album = m_info.album;
-            category = "Musica".to_string();
-        }
-
-        let dest_path_str = enrichment::project_dest_path(
-            dest_base,
-            &file.source_path,
-            file.mime_type.as_deref(),
-            &category,
-            date_val.as_deref(),
-            date_src.as_deref(),
-            artist.as_deref(),
-            album.as_deref(),
-            &file.blake3_hash,
-            &mut used_paths,
-        );
-
-        db::update_enrichment(
-            conn, 
-            file.id, 
-            Some(&category), 
-            confidence, 
-            date_src.as_deref(), 
-            date_val.as_deref(), 
-            artist.as_deref(), 
-            album.as_deref(), 
-            &dest_path_str
-        )?;
+            db::update_metadata(conn, file.id, None, None, m_info.artist.as_deref(), m_info.album.as_deref())?;
+            db::update_classification(conn, file.id, Some(catalog.media_music), None, None, None, None)?;
+        } else {
+            db::update_classification(conn, file.id, Some(catalog.media_other), None, None, None, None)?;
+        }
+
+        while let Ok(outcome) = outcomes.try_recv() {
+            failed += classifications.store(outcome)?;
+        }
+    }
+    while let Some(outcome) = outcomes.recv().await {
+        failed += classifications.store(outcome)?;
+    }
+    if failed > 0 {
+        eprintln!("{}", i18n::fill(catalog.classify_failed, &[&failed]));
     }
-    println!("Phase 3 Complete.");
+
+    // 3c. Projection, from what is now stored for every primary
+    for file in db::get_primary_files(conn)? {
+        // Files with a unique size were never fully hashed
+        let mut hash = file.blake3_hash.clone();
+        if hash.is_none() && layout.uses("hash") {
+            match scanner::full_hash(Path::new(&file.source_path)) {
+                Ok(full) => {
+                    db::set_full_hash(conn, file.id, &full)?;
+                    hash = Some(full);
+                }
+                Err(e) => {
+                    db::update_staging_status(conn, file.id, "ERROR", Some(&e.to_string()))?;
+                    continue;
+                }
+            }
+        }
+
+        let category = file.category.as_deref().unwrap_or(catalog.unknown);
+        let meta = enrichment::FileMeta {
+            source_path: &file.source_path,
+            mime: file.mime_type.as_deref(),
+            category,
+            confidence: file.confidence,
+            date_val: file.date_value.as_deref(),
+            date_src: file.date_source.as_deref(),
+            artist: file.artist.as_deref(),
+            album: file.album.as_deref(),
+            hash: hash.as_deref(),
+        };
+        let projection = enrichment::project_dest_path(dest_base, &layout, &meta);
+        let dest_path_str = match resolve_dest_path(conn, file.id, &file.source_path, hash, projection.dest_path) {
+            Ok(path) => path,
+            // A file that became unreadable since the scan fails on its own
+            Err(e) => {
+                db::update_staging_status(conn, file.id, "ERROR", Some(&e.to_string()))?;
+                continue;
+            }
+        };
+        let sanitized = if projection.sanitized.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&projection.sanitized)?)
+        };
+        db::update_dest_path(conn, file.id, &dest_path_str, sanitized.as_deref())?;
+    }
+    println!("{}", catalog.phase3_done);
     
     // Phase 4: Staging
-    println!("Phase 4: Staging");
+    println!("{}", catalog.phase4);
     if args.dry_run {
-        println!("Dry run enabled. Skipping physical file copies.");
+        println!("{}", catalog.dry_run_skip);
         // Export projected paths report
         let report_path = "dry_run_report.txt";
         let staging_files = db::get_staging_files(conn)?;
         let mut lines = Vec::new();
-        lines.push(format!("{:<80} → {}", "ORIGEN", "DESTINO"));
+        lines.push(format!("{:<80} → {}", catalog.report_source, catalog.report_dest));
         lines.push("-".repeat(160));
         for file in &staging_files {
             if let Some(dest) = &file.dest_path {
@@ -163,13 +292,13 @@ pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
             }
         }
         lines.push(String::new());
-        lines.push(format!("Total archivos proyectados: {}", staging_files.len()));
+        lines.push(i18n::fill(catalog.report_total, &[&staging_files.len()]));
         std::fs::write(report_path, lines.join("\n"))?;
-        println!("Reporte exportado a: {}", report_path);
+        println!("{}", i18n::fill(catalog.report_exported, &[&report_path]));
     } else {
         let staging_files = db::get_staging_files(conn)?;
         for file in staging_files {
-            if let Some(dest) = file.dest_path {
+            if let Some(dest) = &file.dest_path {
                 let dest_path = Path::new(&dest);
                 if let Some(parent) = dest_path.parent() {
                     if let Err(e) = std::fs::create_dir_all(parent) {
@@ -177,18 +306,11 @@ pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
                         continue;
                     }
                 }
-                match std::fs::copy(&file.source_path, &dest_path) {
-                    Ok(_) => {
-                        db::update_staging_status(conn, file.id, "COMPLETADO", None)?;
-                    }
-                    Err(e) => {
-                        db::update_staging_status(conn, file.id, "ERROR", Some(&e.to_string()))?;
-                    }
-                }
+                stage_verified(conn, &file, dest_path, args.copy_retries)?;
             }
         }
     }
-    println!("Phase 4 Complete.");
+    println!("{}", catalog.phase4_done);
     
     // Final Report
     let mut stmt = conn.prepare("SELECT status, count(*) FROM files GROUP BY status")?;
@@ -196,26 +318,509 @@ pub async fn run_pipeline(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
         Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
     })?;
     
-    println!("\n=== Reporte Final ===");
+    println!("\n{}", catalog.final_report);
     for row in rows.flatten() {
         println!("{}: {}", row.0, row.1);
     }
-    println!("=====================\n");
+    println!("{}\n", "=".repeat(catalog.final_report.chars().count()));
     
     Ok(())
 }
 
-pub fn commit(conn: &Connection) -> anyhow::Result<()> {
+/// Images whose classification failed are still staged, under the unknown category. On
+/// `--resume` their copies are taken back out, the same way `rollback` would (only while they
+/// still match an original that is still in place), so they are classified, projected and
+/// staged again like any other primary. Returns how many.
+fn unstage_failed_classifications(conn: &Connection, dest_base: Option<&Path>) -> anyhow::Result<usize> {
+    let mut unstaged = 0;
+    for copy in db::get_completed_copies(conn)? {
+        if copy.classification_error.is_none() {
+            continue;
+        }
+        let dest = Path::new(&copy.dest_path);
+        let source = Path::new(&copy.source_path);
+        if !source.exists() || (dest.exists() && !copy_matches(dest, copy.blake3_hash.as_deref(), source)) {
+            continue;
+        }
+        if dest.exists() {
+            std::fs::remove_file(dest)?;
+            if let Some(root) = dest_base {
+                prune_empty_dirs(dest.parent(), root);
+            }
+        }
+        db::update_staging_status(conn, copy.id, "PRIMARIO", None)?;
+        unstaged += 1;
+    }
+    Ok(unstaged)
+}
+
+/// Suffix of the temporary files copies are written to before being renamed into place.
+const TEMP_SUFFIX: &str = ".ordb-tmp";
+
+/// The hidden temporary file the copy of file `id` is written to, next to its destination.
+fn temp_path(dest: &Path, id: i64) -> PathBuf {
+    dest.with_file_name(format!(".{}{}", id, TEMP_SUFFIX))
+}
+
+/// Copies a file into the destination and only marks it COMPLETADO once the copy's BLAKE3
+/// matches the content scanned. The copy is written to a hidden temporary file next to `dest`,
+/// synced, verified and then renamed, so an interrupted run never leaves a truncated file under
+/// the final name. A mismatch (the source changed, or the write was corrupted) is copied again up
+/// to `retries` times and then left as ERROR_VERIFICACION, for a `--resume` to retry. Read or
+/// write failures are plain ERROR.
+fn stage_verified(conn: &Connection, file: &db::StagingFile, dest: &Path, retries: u32) -> anyhow::Result<()> {
+    let temp = temp_path(dest, file.id);
+    let fail = |status: &str, msg: &str| -> anyhow::Result<()> {
+        let _ = std::fs::remove_file(&temp);
+        db::update_staging_status(conn, file.id, status, Some(msg))?;
+        Ok(())
+    };
+
+    let mut expected = file.blake3_hash.clone();
+    let mut mismatch = String::new();
+    for attempt in 0..=retries {
+        // Whatever an earlier attempt or an interrupted run left at the temp path goes first
+        let _ = std::fs::remove_file(&temp);
+        let read = match scanner::copy_and_hash(Path::new(&file.source_path), &temp) {
+            Ok(hash) => hash,
+            Err(e) => return fail("ERROR", &e.to_string()),
+        };
+        // Files with a unique size were never fully hashed: what was just read is the reference
+        if expected.is_none() {
+            db::set_full_hash(conn, file.id, &read)?;
+            expected = Some(read.clone());
+        }
+        let expected = expected.as_deref().unwrap_or_default();
+        let written = match scanner::full_hash(&temp) {
+            Ok(hash) => hash,
+            Err(e) => return fail("ERROR", &e.to_string()),
+        };
+        if read == expected && written == expected {
+            // The destination may have been taken since Phase 3 chose it, by another program or by
+            // a name a case-insensitive filesystem folds into this one: rename never replaces it
+            let free = match resolve_dest_path(conn, file.id, &file.source_path, Some(expected.to_string()), dest.to_string_lossy().to_string()) {
+                Ok(free) => free,
+                Err(e) => return fail("ERROR", &e.to_string()),
+            };
+            if Path::new(&free) != dest {
+                log::warn!("{} was taken at staging time, using {}", dest.display(), free);
+                db::rename_dest_path(conn, file.id, &free)?;
+            }
+            let dest = Path::new(&free);
+            if let Err(e) = std::fs::rename(&temp, dest).and_then(|_| sync_dir(dest.parent())) {
+                return fail("ERROR", &e.to_string());
+            }
+            // Only now: a read-only temp file could not be replaced by a retry. The verified copy is
+            // already in place, so failing here must not orphan it
+            let permissions = std::fs::metadata(&file.source_path).map(|m| m.permissions());
+            if let Err(e) = permissions.and_then(|p| std::fs::set_permissions(dest, p)) {
+                log::warn!("Could not copy the permissions of {} to {}: {}", file.source_path, dest.display(), e);
+            }
+            db::update_staging_status(conn, file.id, "COMPLETADO", None)?;
+            return Ok(());
+        }
+        mismatch = if read != expected {
+            format!("El origen cambió desde el escaneo (hash {} en vez de {})", read, expected)
+        } else {
+            format!("La copia no coincide con el origen (hash {} en vez de {})", written, expected)
+        };
+        log::warn!("Verification failed for {} (attempt {}/{}): {}", file.source_path, attempt + 1, retries + 1, mismatch);
+    }
+    fail("ERROR_VERIFICACION", &mismatch)
+}
+
+/// Makes a rename inside `dir` durable. Windows cannot open directories for syncing.
+fn sync_dir(dir: Option<&Path>) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = dir {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Deletes the temporary files an interrupted Phase 4 left in the destination. Only the exact
+/// name each recorded file would use is touched, so nothing else in the destination is, whatever
+/// it is called. Returns how many.
+fn remove_stale_temp_files(conn: &Connection) -> anyhow::Result<usize> {
+    let mut removed = 0;
+    for (id, dest_path) in db::get_dest_paths(conn)? {
+        let temp = temp_path(Path::new(&dest_path), id);
+        if temp.is_file() && std::fs::remove_file(&temp).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// The scanner only proves a file unique against the files it saw in this run. On a resumed run
+/// the database may already hold same-sized primaries that were never fully hashed, so any that
+/// could still match get a full hash now, and so does this file if it lacks one. Returns `None`
+/// when the file provably has no duplicate among the primaries.
+fn resolve_full_hash(conn: &Connection, id: i64, file_info: &scanner::FileInfo) -> anyhow::Result<Option<String>> {
+    let candidates: Vec<_> = db::find_primaries_by_size(conn, file_info.size, id)?
+        .into_iter()
+        .filter(|c| match (&c.partial_hash, &file_info.partial_hash) {
+            (Some(theirs), Some(ours)) => theirs == ours,
+            _ => true,
+        })
+        .collect();
+
+    for candidate in candidates.iter().filter(|c| c.blake3_hash.is_none()) {
+        match scanner::full_hash(Path::new(&candidate.source_path)) {
+            Ok(hash) => db::set_full_hash(conn, candidate.id, &hash)?,
+            Err(e) => log::warn!("Could not hash {}: {}", candidate.source_path, e),
+        }
+    }
+
+    match &file_info.hash {
+        Some(hash) => Ok(Some(hash.clone())),
+        None if candidates.is_empty() => Ok(None),
+        None => {
+            let hash = scanner::full_hash(Path::new(&file_info.path))?;
+            db::set_full_hash(conn, id, &hash)?;
+            Ok(Some(hash))
+        }
+    }
+}
+
+/// Picks the first free variant of the projected path: as projected, then with a hash prefix,
+/// then with a counter on top. Both the database and the destination filesystem are checked, so
+/// files from earlier or resumed runs are never overwritten.
+fn resolve_dest_path(conn: &Connection, id: i64, source_path: &str, mut key: Option<String>, projected: String) -> anyhow::Result<String> {
+    let mut candidate = projected.clone();
+    let mut attempt = 0;
+    while !dest_is_free(conn, &candidate, id, source_path)? {
+        // Files with a unique size were never fully hashed; only a collision makes it worth it
+        let key = match &key {
+            Some(key) => key,
+            None => {
+                let hash = scanner::full_hash(Path::new(source_path))?;
+                db::set_full_hash(conn, id, &hash)?;
+                key.insert(hash)
+            }
+        };
+        attempt += 1;
+        let suffix = if attempt == 1 {
+            key[0..8].to_string()
+        } else {
+            format!("{}_{}", &key[0..8], attempt)
+        };
+        candidate = enrichment::with_name_suffix(&projected, &suffix);
+    }
+    Ok(candidate)
+}
+
+fn dest_is_free(conn: &Connection, candidate: &str, id: i64, source_path: &str) -> anyhow::Result<bool> {
+    if let Some(owner) = db::dest_path_owner(conn, candidate)? {
+        if owner != id {
+            return Ok(false);
+        }
+    }
+    // Something already on disk is only acceptable if it is this very file, e.g. copied by an
+    // interrupted run before its status was saved
+    let path = Path::new(candidate);
+    if path.exists() {
+        return Ok(scanner::files_identical(path, Path::new(source_path)).unwrap_or(false));
+    }
+    Ok(true)
+}
+
+/// Applies `--primary-policy` to every duplicate group still waiting to be staged.
+/// Returns how many groups ended up with a different primary.
+fn elect_primaries(args: &Cli, conn: &Connection) -> anyhow::Result<usize> {
+    let preferred: Vec<PathBuf> = args.prefer_source.iter()
+        .map(|p| p.canonicalize().unwrap_or(p.clone()))
+        .collect();
+    let members = db::get_duplicate_groups(conn)?;
+
+    let mut changed = 0;
+    for group in members.chunk_by(|a, b| a.primary_id == b.primary_id) {
+        let current = match group.iter().find(|m| m.id == m.primary_id) {
+            // A projected primary may already have been copied there by an interrupted run;
+            // electing another would leave that copy orphaned
+            Some(m) if m.dest_path.is_some() => continue,
+            Some(m) => m.id,
+            None => continue,
+        };
+        let candidates: Vec<_> = group.iter()
+            .map(|m| policy::Member { id: m.id, path: m.source_path.clone() })
+            .collect();
+        if let Some(elected) = policy::elect(&candidates, &args.primary_policy, &preferred) {
+            if elected != current {
+                let ids: Vec<_> = group.iter().map(|m| m.id).collect();
+                db::set_group_primary(conn, elected, &ids)?;
+                changed += 1;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Fingerprints primary images that do not have one yet, then demotes every near-duplicate to
+/// DUPLICADO_VISUAL pointing at the highest-resolution copy of its group.
+async fn visual_dedup(args: &Cli, conn: &Connection) -> anyhow::Result<()> {
+    let pending = db::get_images_without_phash(conn)?;
+    let threads = scan_threads(args);
+    let fingerprints = tokio::task::spawn_blocking(move || {
+        let results = std::sync::Mutex::new(Vec::new());
+        scanner::for_each_parallel(threads, pending, |(id, path)| {
+            if let Some(fp) = perceptual::fingerprint(Path::new(&path)) {
+                results.lock().unwrap().push((id, fp));
+            }
+            true
+        });
+        results.into_inner().unwrap()
+    }).await?;
+    for (id, fp) in fingerprints {
+        db::set_phash(conn, id, &format!("{:016x}", fp.hash), fp.width, fp.height)?;
+    }
+
+    let candidates = db::get_primary_fingerprints(conn)?
+        .into_iter()
+        .filter_map(|f| {
+            let hash = u64::from_str_radix(&f.phash, 16).ok()?;
+            let pixels = f.width as u64 * f.height as u64;
+            Some(perceptual::Candidate { id: f.id, hash, quality: (pixels, f.file_size) })
+        })
+        .collect();
+    for (leader, members) in perceptual::group_near_duplicates(candidates, args.visual_threshold) {
+        for id in members {
+            db::update_status(conn, id, "DUPLICADO_VISUAL", Some(leader))?;
+        }
+    }
+    Ok(())
+}
+
+/// Where classifications are written: the file rows, and the persistent cache when the service
+/// identifies its model.
+struct Classifications<'a> {
+    conn: &'a Connection,
+    cache: &'a Connection,
+    service: Option<api_client::ServiceInfo>,
+    /// `category_source` of the backend answering
+    source: &'static str,
+    taxonomy_version: &'a str,
+    threshold: f32,
+    catalog: &'a Catalog,
+    /// Source path → (file id, content hash) of every image sent to the service
+    ids: HashMap<String, (i64, Option<String>)>,
+}
+
+impl<'a> Classifications<'a> {
+    fn cache_key(&self) -> Option<(&str, &str, &str)> {
+        let service = self.service.as_ref()?;
+        Some((
+            service.model.as_deref()?,
+            service.model_version.as_deref().unwrap_or(""),
+            self.taxonomy_version,
+        ))
+    }
+
+    fn category<'c>(&self, category: &'c str, confidence: f32) -> &'c str
+    where
+        'a: 'c,
+    {
+        if confidence < self.threshold { self.catalog.unknown } else { category }
+    }
+
+    /// Stores an AI answer: the thresholded category on the file, and every score as a tag.
+    /// Services that send no score list still leave their top category as the single tag.
+    /// Rule answers are kept as they come, without confidence or tags.
+    fn record(&self, id: i64, category: &str, confidence: f32, scores: &[api_client::Score]) -> anyhow::Result<()> {
+        if self.source != "IA" {
+            db::update_classification(self.conn, id, Some(category), None, Some(self.source), None, None)?;
+            return Ok(());
+        }
+        let shown = self.category(category, confidence);
+        let classified_by = db::ClassifiedBy {
+            model: self.service.as_ref().and_then(|s| s.model.as_deref()),
+            model_version: self.service.as_ref().and_then(|s| s.model_version.as_deref()),
+            taxonomy_version: self.taxonomy_version,
+        };
+        db::update_classification(self.conn, id, Some(shown), Some(confidence), Some("IA"), None, Some(&classified_by))?;
+        let tags: Vec<(&str, f32)> = if scores.is_empty() {
+            vec![(category, confidence)]
+        } else {
+            scores.iter().map(|s| (s.category.as_str(), s.score)).collect()
+        };
+        db::replace_file_tags(self.conn, id, &tags)?;
+        Ok(())
+    }
+
+    /// Splits the primary images into those whose AI classification still stands, which only get
+    /// the threshold re-applied, and the rest: new images, failed ones and those answered by
+    /// another model or taxonomy. The rest is answered from the cache where possible; what is
+    /// left over has to be sent to the service.
+    async fn plan(&self, args: &Cli, primary_files: &[db::PrimaryFile]) -> anyhow::Result<Plan> {
+        let mut candidates = Vec::new();
+        let mut stale = 0;
+        for file in primary_files {
+            if !file.mime_type.as_deref().unwrap_or("").starts_with("image/") {
+                continue;
+            }
+            let mut classified = file.category_source.as_deref() == Some("IA") && file.classification_error.is_none();
+            if let (true, Some(current)) = (classified, &self.service) {
+                let outdated = file.model_name != current.model
+                    || file.model_version != current.model_version
+                    || file.taxonomy_version.as_deref() != Some(self.taxonomy_version);
+                if outdated {
+                    classified = false;
+                    stale += 1;
+                }
+            }
+            if classified {
+                self.reapply_threshold(file.id)?;
+            } else {
+                candidates.push((file.id, file.source_path.clone(), file.blake3_hash.clone()));
+            }
+        }
+
+        if self.service.is_some() {
+            hash_missing(args, self.conn, &mut candidates).await?;
+        }
+        let mut pending = Vec::new();
+        let mut cache_hits = 0;
+        for (id, path, hash) in candidates {
+            if self.apply_cached(id, hash.as_deref())? {
+                cache_hits += 1;
+            } else {
+                pending.push((id, path, hash));
+            }
+        }
+        Ok(Plan { pending, stale, cache_hits })
+    }
+
+    /// Applies a cached classification to `id`, if there is one. Returns whether it did.
+    fn apply_cached(&self, id: i64, hash: Option<&str>) -> anyhow::Result<bool> {
+        let (Some((model, version, taxonomy)), Some(hash)) = (self.cache_key(), hash) else {
+            return Ok(false);
+        };
+        match db::get_cached_classification(self.cache, hash, model, version, taxonomy)? {
+            Some(hit) => {
+                let scores: Vec<api_client::Score> = match &hit.scores {
+                    Some(json) => serde_json::from_str(json)?,
+                    None => Vec::new(),
+                };
+                self.record(id, &hit.category, hit.confidence, &scores)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Re-derives the category of an image classified in an earlier run from its stored top tag,
+    /// so a changed `--confidence-threshold` applies without asking the service again.
+    fn reapply_threshold(&self, id: i64) -> anyhow::Result<()> {
+        if let Some((category, score)) = db::get_top_tag(self.conn, id)? {
+            db::update_category(self.conn, id, self.category(&category, score), score)?;
+        }
+        Ok(())
+    }
+
+    /// Writes one batch's classifications as they arrive. Returns how many images failed.
+    fn store(&self, outcome: api_client::BatchOutcome) -> anyhow::Result<usize> {
+        for res in outcome.results {
+            let Some((id, hash)) = self.ids.get(&res.path) else { continue };
+            self.record(*id, &res.category, res.confidence, &res.scores)?;
+
+            // A service that ignored our taxonomy answered with its own; don't cache that under ours
+            let same_taxonomy = res.taxonomy_version.as_deref() == Some(self.taxonomy_version);
+            if let (Some((model, version, taxonomy)), Some(hash), true) = (self.cache_key(), hash, same_taxonomy) {
+                let entry = db::CachedClassification {
+                    scores: (!res.scores.is_empty()).then(|| serde_json::to_string(&res.scores)).transpose()?,
+                    category: res.category,
+                    confidence: res.confidence,
+                };
+                db::put_cached_classification(self.cache, hash, model, version, taxonomy, &entry)?;
+            }
+        }
+        for (path, error) in &outcome.failures {
+            if let Some((id, _)) = self.ids.get(path) {
+                db::update_classification(self.conn, *id, Some(self.catalog.unknown), None, None, Some(error), None)?;
+            }
+        }
+        Ok(outcome.failures.len())
+    }
+}
+
+/// Images still to be classified after `Classifications::plan`, as (id, source path, hash)
+struct Plan {
+    pending: Vec<(i64, String, Option<String>)>,
+    stale: usize,
+    cache_hits: usize,
+}
+
+/// Fills in the full hash of candidates that never got one (unique sizes), so they can be looked
+/// up in the classification cache. Files that cannot be read keep `None` and are simply sent.
+async fn hash_missing(args: &Cli, conn: &Connection, candidates: &mut [(i64, String, Option<String>)]) -> anyhow::Result<()> {
+    let missing: Vec<(i64, String)> = candidates.iter()
+        .filter(|(_, _, hash)| hash.is_none())
+        .map(|(id, path, _)| (*id, path.clone()))
+        .collect();
+    let threads = scan_threads(args);
+    let hashed = tokio::task::spawn_blocking(move || {
+        let results = std::sync::Mutex::new(HashMap::new());
+        scanner::for_each_parallel(threads, missing, |(id, path)| {
+            if let Ok(hash) = scanner::full_hash(Path::new(&path)) {
+                results.lock().unwrap().insert(id, hash);
+            }
+            true
+        });
+        results.into_inner().unwrap()
+    }).await?;
+
+    for (id, _, hash) in candidates.iter_mut() {
+        if let Some(full) = hashed.get(id) {
+            db::set_full_hash(conn, *id, full)?;
+            *hash = Some(full.clone());
+        }
+    }
+    Ok(())
+}
+
+fn service_options(args: &Cli) -> api_client::ServiceOptions {
+    api_client::ServiceOptions {
+        connect_timeout: Duration::from_secs(args.ai_connect_timeout),
+        request_timeout: Duration::from_secs(args.ai_timeout),
+        token: args.ai_token.clone(),
+        ca_cert: args.ai_ca_cert.clone(),
+        insecure: args.ai_insecure,
+    }
+}
+
+fn scan_threads(args: &Cli) -> usize {
+    args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+    })
+}
+
+/// Byte-for-byte check behind `--paranoid`. A duplicate that does not match its primary, or that
+/// cannot be compared, gets its own status so it is never staged or trashed as a duplicate.
+fn verify_duplicate(conn: &Connection, path: &str, primary_id: i64) -> anyhow::Result<(&'static str, Option<String>)> {
+    let primary_path = db::get_source_path(conn, primary_id)?;
+    match scanner::files_identical(Path::new(path), Path::new(&primary_path)) {
+        Ok(true) => Ok(("DUPLICADO_EXACTO", None)),
+        Ok(false) => Ok(("COLISION_HASH", Some(format!("Mismo hash que {} pero contenido distinto", primary_path)))),
+        Err(e) => Ok(("ERROR_COMPARACION", Some(format!("No se pudo comparar con {}: {}", primary_path, e)))),
+    }
+}
+
+pub fn commit(conn: &Connection, per_file: bool, catalog: &Catalog) -> anyhow::Result<()> {
     let sources = db::get_sources(conn)?;
     if sources.is_empty() {
-        println!("No source directories recorded. Run the pipeline first.");
+        println!("{}", catalog.no_sources);
         return Ok(());
     }
+    if per_file {
+        return commit_per_file(conn, &sources, catalog);
+    }
 
     for source in sources {
         let dir = Path::new(&source);
         if !dir.exists() {
-            println!("Source {:?} no longer exists, skipping.", dir);
+            println!("{}", i18n::fill(catalog.source_missing, &[&dir.display()]));
             continue;
         }
         let parent = dir.parent().unwrap_or(Path::new("."));
@@ -225,63 +830,263 @@ pub fn commit(conn: &Connection) -> anyhow::Result<()> {
         }
         let target = trash_dir.join(dir.file_name().unwrap_or_default());
 
-        println!("Moving {:?} to {:?}", dir, target);
-        if let Err(_) = std::fs::rename(&dir, &target) {
-            println!("rename failed (cross-device?), falling back to copy + delete...");
-            copy_dir_recursive(&dir, &target)?;
-            std::fs::remove_dir_all(&dir)?;
+        println!("{}", i18n::fill(catalog.moving, &[&dir.display(), &target.display()]));
+        if std::fs::rename(dir, &target).is_err() {
+            println!("{}", catalog.rename_fallback);
+            copy_dir_recursive(dir, &target)?;
+            std::fs::remove_dir_all(dir)?;
         }
+        db::insert_commit_manifest(conn, None, &source, &target.to_string_lossy())?;
     }
-    println!("Commit successfully completed.");
+    println!("{}", catalog.commit_done);
     Ok(())
 }
 
-pub fn rollback(conn: &Connection) -> anyhow::Result<()> {
-    let mut stmt = conn.prepare("SELECT DISTINCT dest_path FROM files WHERE dest_path IS NOT NULL")?;
-    let paths: rusqlite::Result<Vec<String>> = stmt.query_map([], |row| row.get(0))?.collect();
-    let paths = paths?;
+/// Moves to the trash only the originals whose content is safe in the destination: staged
+/// primaries whose copy still matches, and the exact duplicates of those. Each original is
+/// hashed again first, so one edited since staging, or another file now at its path, stays.
+/// Visual duplicates differ byte for byte and stay where they are too. Each file keeps its path
+/// relative to its source under `_trash_organizador/<source name>`, like a whole-directory
+/// commit, and is recorded in `commit_manifest`. Everything left in the sources is reported, by
+/// reason.
+fn commit_per_file(conn: &Connection, sources: &[String], catalog: &Catalog) -> anyhow::Result<()> {
+    let candidates = db::get_commit_candidates(conn)?;
+    // Every primary's copy is checked before anything moves, while all originals are in place
+    let mut verified = HashMap::new();
+    for candidate in &candidates {
+        verified.entry(candidate.primary_id).or_insert_with(|| {
+            copy_matches(Path::new(&candidate.dest_path), candidate.primary_hash.as_deref(), Path::new(&candidate.primary_source))
+        });
+    }
 
-    let mut top_level_dest = std::collections::HashSet::new();
-    for p in paths {
-        if let Some(top) = extract_top_level_dir(&p) {
-            top_level_dest.insert(top);
+    let roots: Vec<&Path> = sources.iter().map(Path::new).collect();
+    let mut moved = 0;
+    for candidate in &candidates {
+        let source = Path::new(&candidate.source_path);
+        if !verified[&candidate.primary_id] || !source.exists() {
+            continue;
+        }
+        // The same check as for the copy, the other way round: the original against the hash
+        if !copy_matches(source, candidate.primary_hash.as_deref(), Path::new(&candidate.dest_path)) {
+            continue;
         }
+        // The deepest source containing the file decides its place in the trash
+        let Some(root) = roots.iter().filter(|r| source.starts_with(r)).max_by_key(|r| r.components().count()) else {
+            continue;
+        };
+        let target = free_trash_path(trash_dir_for(root).join(source.strip_prefix(root)?));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_path(source, &target)?;
+        db::insert_commit_manifest(conn, Some(candidate.id), &candidate.source_path, &target.to_string_lossy())?;
+        prune_empty_dirs(source.parent(), root);
+        moved += 1;
     }
+    println!("{}", i18n::fill(catalog.commit_moved, &[&moved]));
 
-    if top_level_dest.is_empty() {
-        println!("No destination directories found to rollback.");
-        return Ok(());
+    // Whatever is still in the sources stayed behind: say why
+    let statuses = db::get_statuses_by_path(conn)?;
+    let mut left: std::collections::BTreeMap<String, Vec<String>> = std::collections::BTreeMap::new();
+    for root in roots.iter().filter(|r| r.exists()) {
+        for entry in walkdir::WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path().to_string_lossy().to_string();
+            let reason = match statuses.get(&path).map(String::as_str) {
+                Some("COMPLETADO" | "DUPLICADO_EXACTO") => catalog.left_unverified.to_string(),
+                Some(status) => status.to_string(),
+                None => catalog.left_unscanned.to_string(),
+            };
+            left.entry(reason).or_default().push(path);
+        }
     }
+    if !left.is_empty() {
+        let total: usize = left.values().map(Vec::len).sum();
+        println!("{}", i18n::fill(catalog.left_behind, &[&total]));
+        let report_path = "commit_report.txt";
+        let mut lines = Vec::new();
+        for (reason, paths) in &left {
+            println!("  {}: {}", reason, paths.len());
+            lines.extend(paths.iter().map(|p| format!("{:<24} {}", reason, p)));
+        }
+        std::fs::write(report_path, lines.join("\n"))?;
+        println!("{}", i18n::fill(catalog.report_exported, &[&report_path]));
+    }
+    println!("{}", catalog.commit_done);
+    Ok(())
+}
+
+/// Whether a staged copy still holds its original's content: by hash when there is one, else by
+/// comparing with the original itself.
+fn copy_matches(dest: &Path, hash: Option<&str>, original: &Path) -> bool {
+    match hash {
+        Some(hash) => scanner::full_hash(dest).is_ok_and(|h| h == hash),
+        // Never fully hashed (unique size)
+        None => scanner::files_identical(original, dest).unwrap_or(false),
+    }
+}
+
+fn trash_dir_for(source: &Path) -> PathBuf {
+    let parent = source.parent().unwrap_or(Path::new("."));
+    parent.join("_trash_organizador").join(source.file_name().unwrap_or_default())
+}
+
+/// `target`, or the first `_1`, `_2`, ... variant of it nothing occupies, e.g. a file an earlier
+/// commit trashed and a conflicting `restore` left there.
+fn free_trash_path(target: PathBuf) -> PathBuf {
+    let mut candidate = target.clone();
+    let mut n = 0;
+    // symlink_metadata also catches dangling links
+    while candidate.symlink_metadata().is_ok() {
+        n += 1;
+        candidate = PathBuf::from(enrichment::with_name_suffix(&target.to_string_lossy(), &n.to_string()));
+    }
+    candidate
+}
+
+/// Renames `from` to `to`, copying and deleting when they are on different devices. `from` may
+/// be a file or a directory. Refuses to replace anything already at `to`.
+fn move_path(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+    if std::fs::rename(from, to).is_err() {
+        if from.is_dir() {
+            copy_dir_recursive(from, to)?;
+            std::fs::remove_dir_all(from)?;
+        } else {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes `dir` and its ancestors while they are empty, stopping at `root`, which is kept.
+fn prune_empty_dirs(mut dir: Option<&Path>, root: &Path) {
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
 
-    println!("\nSe eliminarán los siguientes directorios de destino:");
-    for dir in &top_level_dest {
-        println!("  - {:?}", dir);
+/// Deletes the copies Phase 4 made, and nothing else: a copy is only removed while it still
+/// holds its original's content and that original still exists, so neither later edits nor the
+/// only remaining copy of a committed file are lost. Folders left empty are removed up to the
+/// destination. Rolled-back files return to PRIMARIO, ready to be staged again with `--resume`.
+pub fn rollback(conn: &Connection, catalog: &Catalog) -> anyhow::Result<()> {
+    let RollbackPlan { removable, kept } = rollback_plan(conn, catalog)?;
+    for (reason, path) in &kept {
+        println!("{}", i18n::fill(catalog.rollback_kept, &[path, reason]));
     }
-    print!("\n¿Continuar con el rollback? (s/n): ");
+    if removable.is_empty() {
+        println!("{}", catalog.nothing_to_rollback);
+        return Ok(());
+    }
+
+    println!("\n{}", i18n::fill(catalog.rollback_list, &[&removable.len()]));
+    print!("\n{}", catalog.rollback_confirm);
     std::io::Write::flush(&mut std::io::stdout())?;
 
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
-    if input.trim().to_lowercase() != "s" {
-        println!("Rollback cancelado.");
+    if input.trim().to_lowercase() != catalog.yes {
+        println!("{}", catalog.rollback_cancelled);
         return Ok(());
     }
 
-    for dir in top_level_dest {
-        println!("Removing destination dir: {:?}", dir);
-        if dir.exists() {
-            std::fs::remove_dir_all(&dir)?;
+    remove_copies(conn, removable)?;
+    println!("{}", catalog.rollback_done);
+    Ok(())
+}
+
+/// The staged copies `rollback` may delete, and those it keeps with the reason.
+struct RollbackPlan {
+    removable: Vec<db::CompletedCopy>,
+    kept: Vec<(&'static str, String)>,
+}
+
+/// Records whose copy is already gone return to PRIMARIO right away.
+fn rollback_plan(conn: &Connection, catalog: &Catalog) -> anyhow::Result<RollbackPlan> {
+    let mut removable = Vec::new();
+    let mut kept = Vec::new();
+    for copy in db::get_completed_copies(conn)? {
+        let dest = Path::new(&copy.dest_path);
+        let source = Path::new(&copy.source_path);
+        if !dest.exists() {
+            db::update_staging_status(conn, copy.id, "PRIMARIO", None)?;
+        } else if !source.exists() {
+            kept.push((catalog.kept_source_missing, copy.dest_path));
+        } else if !copy_matches(dest, copy.blake3_hash.as_deref(), source) {
+            kept.push((catalog.kept_modified, copy.dest_path));
+        } else {
+            removable.push(copy);
         }
     }
+    Ok(RollbackPlan { removable, kept })
+}
 
-    conn.execute("UPDATE files SET status = 'PENDIENTE', error_msg = NULL", [])?;
-    println!("Rollback successfully completed.");
+fn remove_copies(conn: &Connection, copies: Vec<db::CompletedCopy>) -> anyhow::Result<()> {
+    // Without a recorded destination (older state.db) no folder is removed
+    let destination = db::get_meta(conn, "destination")?.map(PathBuf::from);
+    for copy in copies {
+        let dest = Path::new(&copy.dest_path);
+        std::fs::remove_file(dest)?;
+        db::update_staging_status(conn, copy.id, "PRIMARIO", None)?;
+        if let Some(root) = &destination {
+            prune_empty_dirs(dest.parent(), root);
+        }
+    }
     Ok(())
 }
 
-pub fn purge(conn: &Connection, force: bool) -> anyhow::Result<()> {
+/// Moves everything `commit` put in the trash back to its original path, latest commit first.
+/// An entry whose original path is taken again is a conflict: it stays in the trash, untouched,
+/// and is reported, as is anything no longer in the trash (e.g. after `purge`).
+pub fn restore(conn: &Connection, catalog: &Catalog) -> anyhow::Result<()> {
+    let entries = db::get_unrestored_manifest(conn)?;
+    if entries.is_empty() {
+        println!("{}", catalog.nothing_to_restore);
+        return Ok(());
+    }
+
+    let (mut restored, mut conflicts) = (0, 0);
+    for entry in entries {
+        let trashed = Path::new(&entry.trash_path);
+        let original = Path::new(&entry.source_path);
+        if !trashed.exists() {
+            println!("{}", i18n::fill(catalog.restore_missing, &[&trashed.display()]));
+            continue;
+        }
+        // symlink_metadata also catches dangling links
+        if original.symlink_metadata().is_ok() {
+            println!("{}", i18n::fill(catalog.restore_conflict, &[&original.display(), &trashed.display()]));
+            conflicts += 1;
+            continue;
+        }
+        if let Some(parent) = original.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        println!("{}", i18n::fill(catalog.moving, &[&trashed.display(), &original.display()]));
+        move_path(trashed, original)?;
+        db::mark_restored(conn, entry.id)?;
+        // Per-file entries leave their folders behind in the trash
+        if let Some(root) = trashed.ancestors().find(|a| a.file_name().is_some_and(|n| n == "_trash_organizador")) {
+            prune_empty_dirs(trashed.parent(), root);
+        }
+        restored += 1;
+    }
+    println!("{}", i18n::fill(catalog.restore_done, &[&restored, &conflicts]));
+    Ok(())
+}
+
+pub fn purge(conn: &Connection, force: bool, catalog: &Catalog) -> anyhow::Result<()> {
     if !force {
-        anyhow::bail!("Purge requires --force flag.");
+        anyhow::bail!(catalog.purge_needs_force);
     }
     let sources = db::get_sources(conn)?;
     let mut trash_dirs = std::collections::HashSet::new();
@@ -292,38 +1097,15 @@ pub fn purge(conn: &Connection, force: bool) -> anyhow::Result<()> {
     }
 
     for dir in trash_dirs {
-        println!("Purging trash dir: {:?}", dir);
+        println!("{}", i18n::fill(catalog.purging, &[&dir.display()]));
         if dir.exists() {
             std::fs::remove_dir_all(&dir)?;
         }
     }
-    println!("Purge successfully completed.");
+    println!("{}", catalog.purge_done);
     Ok(())
 }
 
-/// Extract the first meaningful directory from a path.
-/// Works with both absolute (C:\Users\...) and relative (test_data\...) paths.
-fn extract_top_level_dir(path_str: &str) -> Option<PathBuf> {
-    let path = Path::new(path_str);
-    let mut components = path.components();
-    let mut result = PathBuf::new();
-
-    // Consume prefix and root components (for absolute paths)
-    for comp in components.by_ref() {
-        match comp {
-            std::path::Component::Prefix(_) | std::path::Component::RootDir => {
-                result.push(comp);
-            }
-            std::path::Component::Normal(name) => {
-                result.push(name);
-                return Some(result);
-            }
-            _ => {}
-        }
-    }
-    None
-}
-
 fn copy_dir_recursive(src: &Path, dst: &Path) -> anyhow::Result<()> {
     std::fs::create_dir_all(dst)?;
     for entry in walkdir::WalkDir::new(src) {
@@ -338,3 +1120,231 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn test_pipeline_with_mock_classifier() {
+        let root = std::env::temp_dir().join(format!("ordb-test-pipeline-{}", std::process::id()));
+        let (src, dest) = (root.join("src"), root.join("dest"));
+        std::fs::create_dir_all(src.join("copia")).unwrap();
+        for (name, shade) in [("a.png", 10), ("b.png", 200)] {
+            image::RgbImage::from_pixel(4, 4, image::Rgb([shade, 0, 0])).save(src.join(name)).unwrap();
+        }
+        std::fs::copy(src.join("a.png"), src.join("copia").join("a.png")).unwrap();
+        std::fs::write(src.join("notas.txt"), "hola").unwrap();
+
+        let args = Cli::parse_from(["ordb-cli", "-s", src.to_str().unwrap(), "-d", dest.to_str().unwrap()]);
+        let mut config = Config::default();
+        config.classifier.backend = Backend::Mock;
+        let conn = db::init_db(":memory:").unwrap();
+        let cache = db::init_cache_db(":memory:").unwrap();
+        run_pipeline(&args, &config, &conn, &cache).await.unwrap();
+
+        let names: Vec<String> = config.taxonomy.iter().map(|e| e.name.clone()).collect();
+        let mut stmt = conn.prepare(
+            "SELECT category, category_source, model_name, dest_path FROM files
+             WHERE status = 'COMPLETADO' AND mime_type = 'image/png'",
+        ).unwrap();
+        let images: Vec<(String, String, String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        // The copy is a duplicate, so only two images are staged
+        assert_eq!(images.len(), 2);
+        for (category, source, model, dest_path) in &images {
+            assert!(names.contains(category));
+            assert_eq!((source.as_str(), model.as_str()), ("IA", "mock"));
+            assert!(dest_path.contains(category.as_str()) && Path::new(dest_path).exists());
+        }
+        let cached: i64 = cache.query_row("SELECT count(*) FROM classification_cache", [], |row| row.get(0)).unwrap();
+        assert_eq!(cached, 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commit_per_file_moves_only_verified_originals() {
+        let root = std::env::temp_dir().join(format!("ordb-test-commit-{}", std::process::id()));
+        let (src, dest) = (root.join("src"), root.join("dest"));
+        std::fs::create_dir_all(src.join("copia")).unwrap();
+        for name in ["keep.txt", "copia/keep.txt"] {
+            std::fs::write(src.join(name), "keep").unwrap();
+        }
+        std::fs::write(src.join("edited.txt"), "edited").unwrap();
+        std::fs::write(src.join("failed.txt"), "failed").unwrap();
+
+        let args = Cli::parse_from(["ordb-cli", "-s", src.to_str().unwrap(), "-d", dest.to_str().unwrap()]);
+        let mut config = Config::default();
+        config.classifier.backend = Backend::Mock;
+        let conn = db::init_db(":memory:").unwrap();
+        let cache = db::init_cache_db(":memory:").unwrap();
+        run_pipeline(&args, &config, &conn, &cache).await.unwrap();
+
+        let src = src.canonicalize().unwrap();
+        std::fs::write(src.join("edited.txt"), "edited after staging").unwrap();
+        let failed: i64 = conn.query_row("SELECT id FROM files WHERE source_path LIKE '%failed.txt'", [], |row| row.get(0)).unwrap();
+        db::update_staging_status(&conn, failed, "ERROR", Some("disk full")).unwrap();
+        std::fs::write(src.join("new.txt"), "appeared after the scan").unwrap();
+        // Left by an earlier commit whose restore hit a conflict
+        let trash = trash_dir_for(&src);
+        std::fs::create_dir_all(&trash).unwrap();
+        std::fs::write(trash.join("keep.txt"), "older").unwrap();
+
+        commit_per_file(&conn, &db::get_sources(&conn).unwrap(), &i18n::ES).unwrap();
+
+        assert!(!src.join("keep.txt").exists() && !src.join("copia/keep.txt").exists());
+        assert_eq!(std::fs::read_to_string(trash.join("keep.txt")).unwrap(), "older");
+        assert_eq!(std::fs::read_to_string(trash.join("keep_1.txt")).unwrap(), "keep");
+        assert_eq!(std::fs::read_to_string(trash.join("copia/keep.txt")).unwrap(), "keep");
+        for name in ["edited.txt", "failed.txt", "new.txt"] {
+            assert!(src.join(name).exists(), "{} should have stayed", name);
+        }
+        let report = std::fs::read_to_string("commit_report.txt").unwrap();
+        std::fs::remove_file("commit_report.txt").unwrap();
+        let reason = |name: &str| report.lines().find(|l| l.ends_with(name)).map(|l| l.split("  ").next().unwrap().trim().to_string());
+        assert_eq!(reason("edited.txt").as_deref(), Some(i18n::ES.left_unverified));
+        assert_eq!(reason("failed.txt").as_deref(), Some("ERROR"));
+        assert_eq!(reason("new.txt").as_deref(), Some(i18n::ES.left_unscanned));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rollback_removes_only_intact_copies() {
+        let root = std::env::temp_dir().join(format!("ordb-test-rollback-{}", std::process::id()));
+        let (src, dest) = (root.join("src"), root.join("dest"));
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(dest.join("Otros/txt")).unwrap();
+        std::fs::write(dest.join("Otros/txt/mine.txt"), "already there").unwrap();
+        std::fs::write(src.join("a.txt"), "a").unwrap();
+        std::fs::write(src.join("c.md"), "c").unwrap();
+
+        let args = Cli::parse_from(["ordb-cli", "-s", src.to_str().unwrap(), "-d", dest.to_str().unwrap()]);
+        let mut config = Config::default();
+        config.classifier.backend = Backend::Mock;
+        let conn = db::init_db(":memory:").unwrap();
+        let cache = db::init_cache_db(":memory:").unwrap();
+        run_pipeline(&args, &config, &conn, &cache).await.unwrap();
+        let (copy_a, copy_c) = (dest.join("Otros/txt/a.txt"), dest.join("Otros/md/c.md"));
+        assert!(copy_a.exists() && copy_c.exists());
+        std::fs::write(&copy_c, "edited in the destination").unwrap();
+
+        let plan = rollback_plan(&conn, &i18n::ES).unwrap();
+        assert_eq!(plan.kept, [(i18n::ES.kept_modified, copy_c.to_string_lossy().to_string())]);
+        remove_copies(&conn, plan.removable).unwrap();
+        assert!(!copy_a.exists());
+        assert!(copy_c.exists() && dest.join("Otros/txt/mine.txt").exists());
+
+        // Once nothing else is left, folders are pruned up to the destination, which stays
+        std::fs::write(&copy_c, "c").unwrap();
+        std::fs::remove_dir_all(dest.join("Otros/txt")).unwrap();
+        let plan = rollback_plan(&conn, &i18n::ES).unwrap();
+        assert!(plan.kept.is_empty());
+        remove_copies(&conn, plan.removable).unwrap();
+        assert!(dest.exists() && std::fs::read_dir(&dest).unwrap().next().is_none());
+        let staged: i64 = conn.query_row("SELECT count(*) FROM files WHERE status = 'COMPLETADO'", [], |row| row.get(0)).unwrap();
+        assert_eq!(staged, 0);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_survives_later_runs_and_skips_conflicts() {
+        let root = std::env::temp_dir().join(format!("ordb-test-restore-{}", std::process::id()));
+        let (src, later) = (root.join("src"), root.join("later"));
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(&later).unwrap();
+        std::fs::write(src.join("a.txt"), "a").unwrap();
+        std::fs::write(src.join("b.txt"), "b").unwrap();
+        std::fs::write(later.join("c.txt"), "c").unwrap();
+
+        let mut config = Config::default();
+        config.classifier.backend = Backend::Mock;
+        let conn = db::init_db(":memory:").unwrap();
+        let cache = db::init_cache_db(":memory:").unwrap();
+        let run = |source: &Path, dest: &str| Cli::parse_from(["ordb-cli", "-s", source.to_str().unwrap(), "-d", root.join(dest).to_str().unwrap()]);
+        run_pipeline(&run(&src, "dest"), &config, &conn, &cache).await.unwrap();
+        commit_per_file(&conn, &db::get_sources(&conn).unwrap(), &i18n::ES).unwrap();
+        let src = src.canonicalize().unwrap();
+        assert!(!src.join("a.txt").exists() && !src.join("b.txt").exists());
+
+        // Another batch organized afterwards, without --resume
+        db::reset(&conn).unwrap();
+        run_pipeline(&run(&later, "dest2"), &config, &conn, &cache).await.unwrap();
+        // Something new took one of the original paths
+        std::fs::write(src.join("b.txt"), "new b").unwrap();
+
+        restore(&conn, &i18n::ES).unwrap();
+        assert_eq!(std::fs::read_to_string(src.join("a.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(src.join("b.txt")).unwrap(), "new b");
+        assert_eq!(std::fs::read_to_string(trash_dir_for(&src).join("b.txt")).unwrap(), "b");
+        let pending = db::get_unrestored_manifest(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].source_path.ends_with("b.txt"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_dest_path_checks_database_and_disk() {
+        let root = std::env::temp_dir().join(format!("ordb-test-resolve-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let source = root.join("IMG_001.jpg");
+        std::fs::write(&source, "mine").unwrap();
+        let path = |name: &str| root.join(name).to_string_lossy().to_string();
+
+        let conn = db::init_db(":memory:").unwrap();
+        let insert = |p: &str| db::insert_file(&conn, p, 4, None, None, None, "NINGUNO").unwrap().unwrap();
+        let (mine, other) = (insert(source.to_str().unwrap()), insert("/elsewhere/IMG_001.jpg"));
+        let key = || Some("1a2b3c4d5e6f".to_string());
+
+        // Nothing there yet
+        assert_eq!(resolve_dest_path(&conn, mine, source.to_str().unwrap(), key(), path("free.jpg")).unwrap(), path("free.jpg"));
+        // Recorded for another file, though not on disk
+        db::update_dest_path(&conn, other, &path("owned.jpg"), None).unwrap();
+        assert_eq!(resolve_dest_path(&conn, mine, source.to_str().unwrap(), key(), path("owned.jpg")).unwrap(), path("owned_1a2b3c4d.jpg"));
+        // On disk with other content, and then its suffixed name taken too
+        std::fs::write(root.join("disk.jpg"), "theirs").unwrap();
+        std::fs::write(root.join("disk_1a2b3c4d.jpg"), "theirs too").unwrap();
+        assert_eq!(resolve_dest_path(&conn, mine, source.to_str().unwrap(), key(), path("disk.jpg")).unwrap(), path("disk_1a2b3c4d_2.jpg"));
+        // On disk with this very content: a copy an interrupted run left
+        std::fs::write(root.join("same.jpg"), "mine").unwrap();
+        assert_eq!(resolve_dest_path(&conn, mine, source.to_str().unwrap(), key(), path("same.jpg")).unwrap(), path("same.jpg"));
+        // Without a hash to suffix with, a collision needs the source readable
+        assert!(resolve_dest_path(&conn, mine, &path("gone.jpg"), None, path("owned.jpg")).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_staging_never_replaces_taken_dest() {
+        let root = std::env::temp_dir().join(format!("ordb-test-staging-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let (source, dest) = (root.join("IMG_001.jpg"), root.join("dest.jpg"));
+        std::fs::write(&source, "original").unwrap();
+        // Appeared after Phase 3 chose the name
+        std::fs::write(&dest, "someone else's").unwrap();
+
+        let conn = db::init_db(":memory:").unwrap();
+        let id = db::insert_file(&conn, source.to_str().unwrap(), 8, None, None, None, "NINGUNO").unwrap().unwrap();
+        db::update_staging_status(&conn, id, "PRIMARIO", None).unwrap();
+        db::update_dest_path(&conn, id, dest.to_str().unwrap(), None).unwrap();
+        let file = db::get_staging_files(&conn).unwrap().remove(0);
+        stage_verified(&conn, &file, &dest, 0).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "someone else's");
+        let (status, staged): (String, String) = conn.query_row(
+            "SELECT status, dest_path FROM files WHERE id = ?1", [id], |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!(status, "COMPLETADO");
+        assert_ne!(Path::new(&staged), dest);
+        assert_eq!(std::fs::read_to_string(&staged).unwrap(), "original");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
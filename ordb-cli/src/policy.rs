@@ -0,0 +1,116 @@
+//! EN:
+//! Primary selection policy. Within a group of exact duplicates the scanner keeps whichever copy
+//! it happened to reach first; this module re-elects the copy that survives by applying an
+//! ordered list of rules, each one only breaking the ties left by the previous.
+//!
+//! ES:
+//! Política de selección del primario. Dentro de un grupo de duplicados exactos el escáner se
+//! queda con la primera copia que encuentra; este módulo reelige la copia que sobrevive aplicando
+//! una lista ordenada de reglas, donde cada una solo desempata lo que dejó la anterior.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PrimaryRule {
+    /// Copy that lives under the earliest `--prefer-source` directory
+    PreferredSource,
+    /// Copy with an EXIF capture date and the most EXIF fields
+    RichestExif,
+    /// Copy with the oldest modification time
+    OldestMtime,
+    /// Copy with the shortest path
+    ShortestPath,
+}
+
+pub struct Member {
+    pub id: i64,
+    pub path: String,
+}
+
+struct Facts {
+    preferred_rank: usize,
+    exif: (bool, usize),
+    mtime: Option<SystemTime>,
+    path_len: usize,
+}
+
+impl Facts {
+    fn gather(member: &Member, rules: &[PrimaryRule], preferred: &[PathBuf]) -> Facts {
+        let path = Path::new(&member.path);
+        Facts {
+            preferred_rank: preferred.iter().position(|dir| path.starts_with(dir)).unwrap_or(usize::MAX),
+            exif: if rules.contains(&PrimaryRule::RichestExif) { exif_richness(path) } else { (false, 0) },
+            mtime: fs::metadata(path).and_then(|m| m.modified()).ok(),
+            path_len: member.path.chars().count(),
+        }
+    }
+
+    /// `Less` means `self` is the better primary.
+    fn compare(&self, other: &Facts, rule: PrimaryRule) -> Ordering {
+        match rule {
+            PrimaryRule::PreferredSource => self.preferred_rank.cmp(&other.preferred_rank),
+            PrimaryRule::RichestExif => other.exif.cmp(&self.exif),
+            PrimaryRule::OldestMtime => match (self.mtime, other.mtime) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            PrimaryRule::ShortestPath => self.path_len.cmp(&other.path_len),
+        }
+    }
+}
+
+/// Returns the id of the member that should be primary. Ties on every rule go to the lowest id,
+/// i.e. the copy that was scanned first.
+pub fn elect(members: &[Member], rules: &[PrimaryRule], preferred: &[PathBuf]) -> Option<i64> {
+    let facts: Vec<_> = members.iter().map(|m| Facts::gather(m, rules, preferred)).collect();
+    (0..members.len())
+        .min_by(|&a, &b| {
+            rules.iter()
+                .map(|&rule| facts[a].compare(&facts[b], rule))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+                .then(members[a].id.cmp(&members[b].id))
+        })
+        .map(|idx| members[idx].id)
+}
+
+/// (has DateTimeOriginal, number of EXIF fields)
+fn exif_richness(path: &Path) -> (bool, usize) {
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(_) => return (false, 0),
+    };
+    let mut reader = std::io::BufReader::new(file);
+    match exif::Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => (
+            exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY).is_some(),
+            exif.fields().count(),
+        ),
+        Err(_) => (false, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_rules_only_break_ties() {
+        let members = vec![
+            Member { id: 1, path: "/fotos/backup/viejo/IMG_001.jpg".to_string() },
+            Member { id: 2, path: "/fotos/IMG_001.jpg".to_string() },
+            Member { id: 3, path: "/archivo/2020/IMG_001.jpg".to_string() },
+        ];
+        let rules = [PrimaryRule::PreferredSource, PrimaryRule::ShortestPath];
+
+        assert_eq!(elect(&members, &rules, &[]), Some(2));
+        assert_eq!(elect(&members, &rules, &[PathBuf::from("/archivo")]), Some(3));
+        assert_eq!(elect(&members, &[], &[]), Some(1));
+    }
+}
@@ -0,0 +1,129 @@
+//! EN:
+//! Deterministic image classification used when the AI microservice is disabled (`--no-ai`) or
+//! unreachable. Looks at filename keywords, EXIF hints (scene type, screenshot comments,
+//! scanner software) and screen-sized dimensions, and answers with a category from the same
+//! taxonomy the AI uses. Images no rule recognizes are left for the caller to mark as unknown.
+//!
+//! ES:
+//! Clasificación determinista de imágenes usada cuando el microservicio de IA está desactivado
+//! (`--no-ai`) o no responde. Revisa palabras clave del nombre, pistas EXIF (tipo de escena,
+//! comentarios de captura de pantalla, software de escáner) y dimensiones de pantalla, y responde
+//! con una categoría de la misma taxonomía que usa la IA. Las imágenes que ninguna regla reconoce
+//! quedan para que quien llama las marque como desconocidas.
+
+use std::fs;
+use std::path::Path;
+
+/// Filename tokens per category, checked in order. Tokens of 5+ letters also match as prefixes
+/// (`screenshot` matches `screenshots`).
+const KEYWORDS: &[(&str, &[&str])] = &[
+    ("Arte_Graficos", &["screenshot", "captura", "pantallazo", "meme", "wallpaper", "fondo", "sticker"]),
+    ("Documentos_Texto", &["scan", "escaneo", "receipt", "recibo", "factura", "invoice", "ticket", "documento", "document", "dni", "pasaporte", "passport"]),
+    ("Persona_Sola", &["selfie", "retrato", "portrait"]),
+    ("Grupo_Personas", &["grupo", "group", "boda", "wedding", "fiesta", "party", "cumple", "birthday"]),
+    ("Mascotas_Animales", &["perro", "gato", "dog", "cat", "mascota", "pet", "puppy", "kitten"]),
+    ("Comida_Bebida", &["comida", "food", "cena", "dinner", "almuerzo", "lunch", "receta", "recipe"]),
+    ("Paisajes_Naturaleza", &["paisaje", "landscape", "playa", "beach", "montana", "mountain", "sunset", "atardecer"]),
+    ("Vehiculos_Motor", &["coche", "auto", "car", "moto", "motorcycle", "camion", "truck"]),
+];
+
+/// Common phone and monitor resolutions, in either orientation.
+const SCREEN_SIZES: &[(u32, u32)] = &[
+    (1280, 720), (1366, 768), (1440, 900), (1536, 864), (1600, 900), (1680, 1050),
+    (1920, 1080), (1920, 1200), (2560, 1440), (2560, 1600), (2880, 1800), (3840, 2160),
+    (750, 1334), (828, 1792), (1080, 1920), (1080, 2340), (1080, 2400), (1125, 2436),
+    (1170, 2532), (1179, 2556), (1242, 2688), (1284, 2778), (1290, 2796), (1440, 3200),
+];
+
+#[derive(Default)]
+struct ExifHints {
+    has_camera: bool,
+    screenshot: bool,
+    scanner: bool,
+    scene: Option<u32>,
+}
+
+/// Returns the category for an image, or `None` when no rule applies. `dimensions` are
+/// `(width, height)` in pixels, when known.
+pub fn classify(path: &Path, mime: &str, dimensions: Option<(u32, u32)>) -> Option<&'static str> {
+    if let Some(category) = by_keyword(path) {
+        return Some(category);
+    }
+
+    let hints = exif_hints(path);
+    if hints.screenshot {
+        return Some("Arte_Graficos");
+    }
+    if hints.scanner {
+        return Some("Documentos_Texto");
+    }
+    match hints.scene {
+        Some(1) => return Some("Paisajes_Naturaleza"),
+        Some(2) => return Some("Persona_Sola"),
+        _ => {}
+    }
+
+    // A camera never produces a PNG at exactly a screen resolution
+    let screen_sized = dimensions.is_some_and(|(w, h)| {
+        SCREEN_SIZES.iter().any(|&(a, b)| (w, h) == (a, b) || (w, h) == (b, a))
+    });
+    if screen_sized && !hints.has_camera && mime == "image/png" {
+        return Some("Arte_Graficos");
+    }
+    None
+}
+
+fn by_keyword(path: &Path) -> Option<&'static str> {
+    let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+    let tokens: Vec<&str> = stem.split(|c: char| !c.is_alphabetic()).filter(|t| !t.is_empty()).collect();
+    KEYWORDS.iter().find_map(|(category, words)| {
+        let hit = tokens.iter().any(|token| {
+            words.iter().any(|word| *token == *word || (word.len() >= 5 && token.starts_with(word)))
+        });
+        hit.then_some(*category)
+    })
+}
+
+fn exif_hints(path: &Path) -> ExifHints {
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(_) => return ExifHints::default(),
+    };
+    let mut reader = std::io::BufReader::new(file);
+    let exif = match exif::Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => exif,
+        Err(_) => return ExifHints::default(),
+    };
+    let text = |tag| {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .map(|f| f.display_value().to_string().to_lowercase())
+            .unwrap_or_default()
+    };
+    let software = text(exif::Tag::Software);
+    ExifHints {
+        has_camera: !text(exif::Tag::Make).is_empty() || !text(exif::Tag::Model).is_empty(),
+        screenshot: text(exif::Tag::UserComment).contains("screenshot"),
+        scanner: software.contains("scan"),
+        scene: exif.get_field(exif::Tag::SceneCaptureType, exif::In::PRIMARY)
+            .and_then(|f| f.value.get_uint(0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_without_ai() {
+        let missing = |name: &str| Path::new("/no/such/dir").join(name);
+
+        assert_eq!(classify(&missing("Screenshot_20230101-1200.png"), "image/png", None), Some("Arte_Graficos"));
+        assert_eq!(classify(&missing("factura_luz_enero.jpg"), "image/jpeg", None), Some("Documentos_Texto"));
+        assert_eq!(classify(&missing("mi_perro.jpg"), "image/jpeg", None), Some("Mascotas_Animales"));
+        // "cat" only matches as a whole token
+        assert_eq!(classify(&missing("location.jpg"), "image/jpeg", None), None);
+        assert_eq!(classify(&missing("IMG_0001.png"), "image/png", Some((1170, 2532))), Some("Arte_Graficos"));
+        assert_eq!(classify(&missing("IMG_0001.jpg"), "image/jpeg", Some((1170, 2532))), None);
+        assert_eq!(classify(&missing("IMG_0002.png"), "image/png", Some((4032, 3024))), None);
+    }
+}
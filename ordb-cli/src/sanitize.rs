@@ -0,0 +1,100 @@
+//! EN:
+//! Makes projected path components safe on every filesystem the destination may live on.
+//! Tag values and AI categories are free text: a `/` would create a bogus nested directory and
+//! `:`, `?`, trailing dots or control characters make the copy fail on Windows/exFAT drives.
+//! Components are normalized to Unicode NFC, reserved characters become `_`, Windows device
+//! names (`CON`, `LPT1`, ...) get a `_` suffix and overlong names are cut, keeping the extension.
+//!
+//! ES:
+//! Hace que los componentes de ruta proyectados sean válidos en cualquier sistema de archivos de
+//! destino. Las etiquetas y categorías de la IA son texto libre: una `/` crearía un directorio
+//! anidado falso y `:`, `?`, puntos finales o caracteres de control hacen fallar la copia en
+//! unidades Windows/exFAT. Los componentes se normalizan a Unicode NFC, los caracteres reservados
+//! pasan a `_`, los nombres de dispositivo de Windows (`CON`, `LPT1`, ...) reciben un sufijo `_`
+//! y los nombres demasiado largos se recortan conservando la extensión.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest component, in bytes, accepted by ext4, APFS and NTFS alike.
+pub const MAX_COMPONENT_BYTES: usize = 255;
+
+/// Extensions longer than this are treated as part of the name when truncating.
+const MAX_KEPT_EXTENSION: usize = 16;
+
+const RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Returns a single path component that is safe to create anywhere. Idempotent.
+pub fn sanitize_component(value: &str) -> String {
+    let replaced: String = value
+        .nfc()
+        .map(|c| if c.is_control() || RESERVED_CHARS.contains(&c) { '_' } else { c })
+        .collect();
+
+    // Windows strips trailing dots and spaces, so `Vol. 2.` and `Vol. 2` would collide
+    let trimmed = replaced.trim_start_matches(' ').trim_end_matches(['.', ' ']);
+    let mut name = if trimmed.is_empty() { "_".to_string() } else { trimmed.to_string() };
+
+    let device = name.split('.').next().unwrap_or_default().trim_end().to_ascii_uppercase();
+    if RESERVED_NAMES.contains(&device.as_str()) {
+        name.insert(device.len(), '_');
+    }
+
+    if name.len() > MAX_COMPONENT_BYTES {
+        name = truncate_keeping_extension(&name, MAX_COMPONENT_BYTES);
+    }
+    name
+}
+
+/// Cuts `name` to at most `max_bytes`, shortening the stem so a short extension survives.
+pub fn truncate_keeping_extension(name: &str, max_bytes: usize) -> String {
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot <= MAX_KEPT_EXTENSION => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let budget = max_bytes.saturating_sub(ext.len());
+    let stem = truncate_bytes(stem, budget).trim_end_matches(['.', ' ']);
+    format!("{}{}", stem, ext)
+}
+
+/// Longest prefix of `value` that fits in `max_bytes` without splitting a character.
+pub fn truncate_bytes(value: &str, max_bytes: usize) -> &str {
+    if value.len() <= max_bytes {
+        return value;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(sanitize_component("AC/DC"), "AC_DC");
+        assert_eq!(sanitize_component("What? Why: Now*"), "What_ Why_ Now_");
+        assert_eq!(sanitize_component("Vol. 2. "), "Vol. 2");
+        assert_eq!(sanitize_component("Line\nBreak\t"), "Line_Break_");
+        assert_eq!(sanitize_component(".."), "_");
+        assert_eq!(sanitize_component("con"), "con_");
+        assert_eq!(sanitize_component("LPT1.txt"), "LPT1_.txt");
+        assert_eq!(sanitize_component("Console"), "Console");
+        // Decomposed "é" (e + combining acute) becomes the single precomposed character
+        assert_eq!(sanitize_component("Caf\u{65}\u{301}"), "Caf\u{e9}");
+
+        let long = format!("{}.flac", "ñ".repeat(200));
+        let cut = sanitize_component(&long);
+        assert!(cut.len() <= MAX_COMPONENT_BYTES);
+        assert!(cut.ends_with("ñ.flac"));
+        assert_eq!(sanitize_component(&cut), cut);
+    }
+}
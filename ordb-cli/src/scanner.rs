@@ -1,21 +1,75 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::collections::HashMap;
+use std::sync::{mpsc as std_mpsc, Mutex};
 use walkdir::WalkDir;
 use tokio::sync::mpsc;
 use log::info;
 
+/// Bytes read from each end of a file for the partial (head/tail) hash.
+const PARTIAL_CHUNK: u64 = 64 * 1024;
+
+/// How much of a file's content was hashed during the scan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashLevel {
+    /// Its size was unique, so no content was hashed at all.
+    SizeOnly,
+    /// Only the head/tail hash was needed to tell it apart from same-sized files.
+    Partial,
+    /// Full BLAKE3 of the whole content.
+    Full,
+}
+
+impl HashLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashLevel::SizeOnly => "NINGUNO",
+            HashLevel::Partial => "PARCIAL",
+            HashLevel::Full => "COMPLETO",
+        }
+    }
+}
+
 pub struct FileInfo {
     pub path: String,
     pub size: u64,
     pub mime_type: Option<String>,
     pub hash: Option<String>,
+    pub partial_hash: Option<String>,
+    pub hash_level: HashLevel,
     pub skip_reason: Option<String>,
 }
 
-pub async fn scan_directories(sources: Vec<PathBuf>) -> mpsc::Receiver<FileInfo> {
+pub struct ScanOptions {
+    /// Number of worker threads hashing files concurrently.
+    pub threads: usize,
+    /// Files at least this large are hashed with BLAKE3's mmap + rayon mode.
+    pub large_file_threshold: Option<u64>,
+}
+
+struct Candidate {
+    entry_path: PathBuf,
+    path: String,
+    size: u64,
+}
+
+struct HashJob {
+    candidate: Candidate,
+    partial_hash: Option<String>,
+    level: HashLevel,
+}
+
+/// Walks every source first so files can be grouped by size: a file whose size is unique
+/// cannot have an exact duplicate, and a unique head/tail hash within a size group rules one
+/// out as well. Only the remaining candidates get a full BLAKE3 pass.
+pub async fn scan_directories(sources: Vec<PathBuf>, options: ScanOptions) -> mpsc::Receiver<FileInfo> {
     let (tx, rx) = mpsc::channel(100);
 
     tokio::task::spawn_blocking(move || {
+        let threads = options.threads.max(1);
+        let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+
         for source in sources {
             info!("Scanning directory: {:?}", source);
             for entry in WalkDir::new(&source).into_iter().filter_map(|e| e.ok()) {
@@ -28,63 +82,297 @@ pub async fn scan_directories(sources: Vec<PathBuf>) -> mpsc::Receiver<FileInfo>
 
                     // 0-byte files: emit as skippable
                     if size == 0 {
-                        let info = FileInfo {
-                            path,
-                            size,
-                            mime_type: None,
-                            hash: None,
-                            skip_reason: Some("Archivo vacío (0 bytes)".to_string()),
-                        };
-                        if tx.blocking_send(info).is_err() { break; }
+                        let info = skipped(path, size, "Archivo vacío (0 bytes)".to_string());
+                        if tx.blocking_send(info).is_err() { return; }
                         continue;
                     }
 
-                    // Try to open and hash the file
-                    let file = match fs::File::open(entry.path()) {
-                        Ok(f) => f,
-                        Err(e) => {
-                            let info = FileInfo {
-                                path,
-                                size,
-                                mime_type: None,
-                                hash: None,
-                                skip_reason: Some(format!("No se pudo abrir: {}", e)),
-                            };
-                            if tx.blocking_send(info).is_err() { break; }
-                            continue;
-                        }
-                    };
-
-                    let mut hasher = blake3::Hasher::new();
-                    if let Err(e) = std::io::copy(&mut std::io::BufReader::new(file), &mut hasher) {
-                        let info = FileInfo {
-                            path,
-                            size,
-                            mime_type: None,
-                            hash: None,
-                            skip_reason: Some(format!("Error al leer contenido: {}", e)),
-                        };
-                        if tx.blocking_send(info).is_err() { break; }
-                        continue;
-                    }
+                    by_size.entry(size).or_default().push(Candidate { entry_path: entry.into_path(), path, size });
+                }
+            }
+        }
 
-                    let hash = hasher.finalize().to_hex().to_string();
-                    let mime_type = infer::get_from_path(entry.path())
-                        .ok().flatten().map(|m| m.mime_type().to_string());
+        let mut jobs = Vec::new();
+        let mut partial_candidates = Vec::new();
+        for (size, group) in by_size {
+            if group.len() == 1 {
+                jobs.extend(group.into_iter().map(|candidate| HashJob { candidate, partial_hash: None, level: HashLevel::SizeOnly }));
+            } else if size <= PARTIAL_CHUNK * 2 {
+                // Head and tail would cover the whole file anyway
+                jobs.extend(group.into_iter().map(|candidate| HashJob { candidate, partial_hash: None, level: HashLevel::Full }));
+            } else {
+                partial_candidates.extend(group);
+            }
+        }
+        info!("{} files need a full hash check, {} need a head/tail check first", jobs.len(), partial_candidates.len());
 
-                    let info = FileInfo {
-                        path,
-                        size,
-                        mime_type,
-                        hash: Some(hash),
-                        skip_reason: None,
-                    };
+        let partials = Mutex::new(Vec::new());
+        for_each_parallel(threads, partial_candidates, |candidate| {
+            let result = partial_hash(&candidate.entry_path, candidate.size);
+            partials.lock().unwrap().push((candidate, result));
+            true
+        });
 
-                    if tx.blocking_send(info).is_err() { break; }
+        let mut by_partial: HashMap<String, Vec<Candidate>> = HashMap::new();
+        for (candidate, result) in partials.into_inner().unwrap() {
+            match result {
+                Ok(partial) => by_partial.entry(partial).or_default().push(candidate),
+                Err(e) => {
+                    let info = skipped(candidate.path, candidate.size, format!("Error al leer contenido: {}", e));
+                    if tx.blocking_send(info).is_err() { return; }
                 }
             }
         }
+        for (partial, group) in by_partial {
+            let level = if group.len() == 1 { HashLevel::Partial } else { HashLevel::Full };
+            jobs.extend(group.into_iter().map(|candidate| HashJob { candidate, partial_hash: Some(partial.clone()), level }));
+        }
+
+        for_each_parallel(threads, jobs, |job| tx.blocking_send(hash_job(job, &options)).is_ok());
     });
 
     rx
 }
+
+/// Runs `f` over `items` on `threads` worker threads. `f` returns false to stop early.
+pub fn for_each_parallel<T: Send>(threads: usize, items: Vec<T>, f: impl Fn(T) -> bool + Sync) {
+    let (job_tx, job_rx) = std_mpsc::sync_channel::<T>(threads * 4);
+    // `None` once a worker stopped early: the queue is gone and nobody takes jobs any more
+    let job_rx = Mutex::new(Some(job_rx));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let job_rx = &job_rx;
+            let f = &f;
+            scope.spawn(move || loop {
+                // The lock is only held while waiting for the next job, not while hashing
+                let job = match job_rx.lock().unwrap().as_ref().map(|rx| rx.recv()) {
+                    Some(Ok(job)) => job,
+                    _ => break,
+                };
+                if !f(job) {
+                    // Dropping the queue fails the feeder's send, which would otherwise block
+                    // forever on a full queue with no worker left to drain it
+                    job_rx.lock().unwrap().take();
+                    break;
+                }
+            });
+        }
+
+        for item in items {
+            if job_tx.send(item).is_err() { break; }
+        }
+        // Closing the job queue lets the workers drain it and exit
+        drop(job_tx);
+    });
+}
+
+fn skipped(path: String, size: u64, reason: String) -> FileInfo {
+    FileInfo {
+        path,
+        size,
+        mime_type: None,
+        hash: None,
+        partial_hash: None,
+        hash_level: HashLevel::SizeOnly,
+        skip_reason: Some(reason),
+    }
+}
+
+fn hash_job(job: HashJob, options: &ScanOptions) -> FileInfo {
+    let HashJob { candidate: Candidate { entry_path, path, size }, partial_hash, level } = job;
+
+    let mut hash = None;
+    if level == HashLevel::Full {
+        let result = match options.large_file_threshold {
+            Some(threshold) if size >= threshold => hash_large_file(&entry_path),
+            _ => {
+                // Try to open and hash the file
+                let file = match fs::File::open(&entry_path) {
+                    Ok(f) => f,
+                    Err(e) => return skipped(path, size, format!("No se pudo abrir: {}", e)),
+                };
+                let mut hasher = blake3::Hasher::new();
+                std::io::copy(&mut std::io::BufReader::new(file), &mut hasher)
+                    .map(|_| hasher.finalize().to_hex().to_string())
+            }
+        };
+        match result {
+            Ok(h) => hash = Some(h),
+            Err(e) => return skipped(path, size, format!("Error al leer contenido: {}", e)),
+        }
+    }
+
+    let mime_type = infer::get_from_path(&entry_path)
+        .ok().flatten().map(|m| m.mime_type().to_string());
+
+    FileInfo {
+        path,
+        size,
+        mime_type,
+        hash,
+        partial_hash,
+        hash_level: level,
+        skip_reason: None,
+    }
+}
+
+/// Full BLAKE3 of a file, for files whose hash was skipped during the scan.
+pub fn full_hash(path: &Path) -> std::io::Result<String> {
+    let file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut std::io::BufReader::new(file), &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Copies `from` to `to` and returns the BLAKE3 of the bytes read, so the source is only read
+/// once. The copy is synced to disk before returning. Permissions are left to the caller, so a
+/// read-only source cannot lock out the next attempt at the same path.
+pub fn copy_and_hash(from: &Path, to: &Path) -> std::io::Result<String> {
+    let mut reader = std::io::BufReader::new(fs::File::open(from)?);
+    let mut writer = std::io::BufWriter::new(fs::File::create(to)?);
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; PARTIAL_CHUNK as usize];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Hashes the size plus the first and last `PARTIAL_CHUNK` bytes.
+pub fn partial_hash(path: &Path, size: u64) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&size.to_le_bytes());
+
+    let mut buf = vec![0u8; PARTIAL_CHUNK.min(size) as usize];
+    file.read_exact(&mut buf)?;
+    hasher.update(&buf);
+    if size > PARTIAL_CHUNK {
+        let tail = PARTIAL_CHUNK.min(size - PARTIAL_CHUNK);
+        file.seek(SeekFrom::End(-(tail as i64)))?;
+        buf.truncate(tail as usize);
+        file.read_exact(&mut buf)?;
+        hasher.update(&buf);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Compares two files byte by byte.
+pub fn files_identical(a: &Path, b: &Path) -> std::io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut reader_a = std::io::BufReader::new(fs::File::open(a)?);
+    let mut reader_b = std::io::BufReader::new(fs::File::open(b)?);
+    let mut buf_a = vec![0u8; PARTIAL_CHUNK as usize];
+    let mut buf_b = vec![0u8; PARTIAL_CHUNK as usize];
+    loop {
+        let n = read_full(&mut reader_a, &mut buf_a)?;
+        if read_full(&mut reader_b, &mut buf_b)? != n || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Like `read_exact`, but a short read at end of file is not an error.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Memory-maps the file and spreads the hashing over the rayon thread pool.
+fn hash_large_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_mmap_rayon(path)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_identical_detects_late_difference() {
+        let dir = std::env::temp_dir().join(format!("ordb_identical_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.bin");
+        let b = dir.join("b.bin");
+        let c = dir.join("c.bin");
+
+        let mut content = vec![7u8; 200_000];
+        fs::write(&a, &content).unwrap();
+        fs::write(&b, &content).unwrap();
+        content[199_999] = 8;
+        fs::write(&c, &content).unwrap();
+
+        assert!(files_identical(&a, &b).unwrap());
+        assert!(!files_identical(&a, &c).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scan_hashes_only_what_could_be_a_duplicate() {
+        let dir = std::env::temp_dir().join(format!("ordb_levels_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let size = (PARTIAL_CHUNK * 3) as usize;
+        // Same size, same head and tail, different middle: only a full hash tells them apart
+        let mut same_ends = vec![1u8; size];
+        fs::write(dir.join("a.bin"), &same_ends).unwrap();
+        same_ends[size / 2] = 2;
+        fs::write(dir.join("b.bin"), &same_ends).unwrap();
+        // Same size, different head
+        let mut other_head = vec![1u8; size];
+        other_head[0] = 3;
+        fs::write(dir.join("c.bin"), &other_head).unwrap();
+        fs::write(dir.join("unique.bin"), "a size no other file has").unwrap();
+
+        let options = ScanOptions { threads: 2, large_file_threshold: None };
+        let mut rx = scan_directories(vec![dir.clone()], options).await;
+        let mut found = HashMap::new();
+        while let Some(info) = rx.recv().await {
+            let name = Path::new(&info.path).file_name().unwrap().to_string_lossy().to_string();
+            found.insert(name, info);
+        }
+
+        let unique = &found["unique.bin"];
+        assert_eq!(unique.hash_level, HashLevel::SizeOnly);
+        assert!(unique.hash.is_none() && unique.partial_hash.is_none());
+        let c = &found["c.bin"];
+        assert_eq!(c.hash_level, HashLevel::Partial);
+        assert!(c.hash.is_none() && c.partial_hash.is_some());
+        let (a, b) = (&found["a.bin"], &found["b.bin"]);
+        assert_eq!((a.hash_level, b.hash_level), (HashLevel::Full, HashLevel::Full));
+        assert_eq!(a.partial_hash, b.partial_hash);
+        assert!(a.hash.is_some() && b.hash.is_some() && a.hash != b.hash);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_for_each_parallel_stops_early() {
+        let seen = std::sync::atomic::AtomicUsize::new(0);
+        // Far more items than the queue holds; returns once the first worker gives up
+        for_each_parallel(2, (0..1000).collect(), |_| {
+            seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            false
+        });
+        assert!(seen.into_inner() <= 2);
+    }
+}
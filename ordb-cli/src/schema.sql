@@ -4,21 +4,56 @@ CREATE TABLE IF NOT EXISTS files (
     file_size     INTEGER NOT NULL,
     mime_type     TEXT,
     blake3_hash   TEXT,
+    partial_hash  TEXT,
+    hash_level    TEXT NOT NULL DEFAULT 'NINGUNO',
+    phash         TEXT,
+    image_width   INTEGER,
+    image_height  INTEGER,
     status        TEXT NOT NULL DEFAULT 'PENDIENTE', 
     primary_id    INTEGER REFERENCES files(id),
     category      TEXT,
     confidence    REAL,
+    category_source TEXT,
+    classification_error TEXT,
+    model_name    TEXT,
+    model_version TEXT,
+    taxonomy_version TEXT,
     date_source   TEXT,
     date_value    TEXT,
     dest_path     TEXT,
     artist        TEXT,
     album         TEXT,
+    sanitized_components TEXT,
     error_msg     TEXT,
     created_at    DATETIME DEFAULT CURRENT_TIMESTAMP
 );
 CREATE INDEX IF NOT EXISTS idx_hash ON files(blake3_hash);
 CREATE INDEX IF NOT EXISTS idx_status ON files(status);
+CREATE INDEX IF NOT EXISTS idx_size ON files(file_size);
+-- Every score the classifier gave an image, best first (rank 1 is the top category)
+CREATE TABLE IF NOT EXISTS file_tags (
+    file_id  INTEGER NOT NULL REFERENCES files(id),
+    category TEXT NOT NULL,
+    score    REAL NOT NULL,
+    rank     INTEGER NOT NULL,
+    PRIMARY KEY (file_id, category)
+);
 CREATE TABLE IF NOT EXISTS sources (
     id   INTEGER PRIMARY KEY,
     path TEXT NOT NULL UNIQUE
 );
+-- Where `commit` moved each original (a file, or a whole source directory when file_id is NULL),
+-- so `restore` can move it back
+CREATE TABLE IF NOT EXISTS commit_manifest (
+    id          INTEGER PRIMARY KEY,
+    file_id     INTEGER REFERENCES files(id),
+    source_path TEXT NOT NULL,
+    trash_path  TEXT NOT NULL,
+    restored_at DATETIME,
+    created_at  DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+-- Small facts about the run, e.g. the destination it staged into
+CREATE TABLE IF NOT EXISTS meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,166 @@
+//! EN:
+//! Destination layout templates, e.g. `{media}/{year}/{month:02}_{month_name}/{category}/{filename}`.
+//! A template is split on `/` into path segments. Placeholders accept a zero-padding width
+//! (`{month:02}`) and a fallback value (`{artist|Unknown}`). A segment that uses a variable with
+//! no value and no fallback is left out entirely, so optional levels simply disappear. The last
+//! segment names the file and is always kept.
+//!
+//! ES:
+//! Plantillas de estructura de destino, p. ej. `{media}/{year}/{month:02}_{month_name}/{category}/{filename}`.
+//! Una plantilla se divide por `/` en segmentos de ruta. Los marcadores aceptan un ancho con ceros
+//! (`{month:02}`) y un valor por defecto (`{artist|Desconocido}`). Un segmento que usa una variable
+//! sin valor y sin valor por defecto se omite por completo, así los niveles opcionales desaparecen.
+//! El último segmento da nombre al archivo y siempre se conserva.
+
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Every variable a template may reference.
+pub const VARIABLES: &[&str] = &[
+    "media", "category", "confidence",
+    "year", "month", "day", "month_name", "date_status", "date_source",
+    "artist", "album",
+    "filename", "stem", "ext", "mime", "hash",
+];
+
+/// Variables that put the file's own name in the last segment.
+const NAME_VARIABLES: &[&str] = &["filename", "stem", "hash"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TemplateError {
+    #[error("unclosed '{{' in template {0:?}")]
+    Unclosed(String),
+    #[error("unknown variable {{{0}}}; available: {1}")]
+    UnknownVariable(String, String),
+    #[error("invalid padding {0:?} in template")]
+    InvalidPadding(String),
+    #[error("template {0:?} must end with a segment containing {{filename}}, {{stem}} or {{hash}}")]
+    NoFileName(String),
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Literal(String),
+    Var {
+        name: String,
+        pad: Option<usize>,
+        default: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Vec<Piece>>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut segments = Vec::new();
+        for raw in source.split('/').filter(|s| !s.is_empty()) {
+            segments.push(parse_segment(raw, source)?);
+        }
+
+        let names_file = segments.last().is_some_and(|last| {
+            last.iter().any(|p| matches!(p, Piece::Var { name, .. } if NAME_VARIABLES.contains(&name.as_str())))
+        });
+        if !names_file {
+            return Err(TemplateError::NoFileName(source.to_string()));
+        }
+        Ok(Template { segments })
+    }
+
+    pub fn uses(&self, variable: &str) -> bool {
+        self.segments.iter().flatten().any(|p| matches!(p, Piece::Var { name, .. } if name == variable))
+    }
+
+    /// Renders every segment whose variables all resolve. The file name segment is always kept,
+    /// with missing variables left empty. Values are inserted verbatim; callers are responsible
+    /// for making them safe as path components.
+    pub fn render(&self, vars: &HashMap<&str, String>) -> Vec<String> {
+        let mut rendered = Vec::new();
+        let last = self.segments.len().saturating_sub(1);
+        'segments: for (idx, segment) in self.segments.iter().enumerate() {
+            let mut out = String::new();
+            for piece in segment {
+                match piece {
+                    Piece::Literal(text) => out.push_str(text),
+                    Piece::Var { name, pad, default } => {
+                        let value = match vars.get(name.as_str()).filter(|v| !v.is_empty()).or(default.as_ref()) {
+                            Some(value) => value,
+                            None if idx == last => continue,
+                            None => continue 'segments,
+                        };
+                        match (pad, value.parse::<u64>()) {
+                            (Some(width), Ok(n)) => out.push_str(&format!("{:0width$}", n, width = *width)),
+                            _ => out.push_str(value),
+                        }
+                    }
+                }
+            }
+            if !out.is_empty() {
+                rendered.push(out);
+            }
+        }
+        rendered
+    }
+}
+
+fn parse_segment(raw: &str, source: &str) -> Result<Vec<Piece>, TemplateError> {
+    let mut pieces = Vec::new();
+    let mut rest = raw;
+    while let Some(open) = rest.find('{') {
+        if open > 0 {
+            pieces.push(Piece::Literal(rest[..open].to_string()));
+        }
+        let close = rest[open..].find('}').ok_or_else(|| TemplateError::Unclosed(source.to_string()))? + open;
+        pieces.push(parse_placeholder(&rest[open + 1..close])?);
+        rest = &rest[close + 1..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Literal(rest.to_string()));
+    }
+    Ok(pieces)
+}
+
+fn parse_placeholder(inner: &str) -> Result<Piece, TemplateError> {
+    let (spec, default) = match inner.split_once('|') {
+        Some((spec, default)) => (spec, Some(default.to_string())),
+        None => (inner, None),
+    };
+    let (name, pad) = match spec.split_once(':') {
+        Some((name, pad)) => {
+            let width = pad.parse::<usize>().map_err(|_| TemplateError::InvalidPadding(pad.to_string()))?;
+            (name.trim(), Some(width))
+        }
+        None => (spec.trim(), None),
+    };
+    if !VARIABLES.contains(&name) {
+        return Err(TemplateError::UnknownVariable(name.to_string(), VARIABLES.join(", ")));
+    }
+    Ok(Piece::Var { name: name.to_string(), pad, default })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_omits_segments_without_values() {
+        let template = Template::parse("{media}/{date_status}/{year}/{month:02}_{month_name}/{artist|Anon}/{filename}").unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("media", "Imagenes".to_string());
+        vars.insert("year", "2023".to_string());
+        vars.insert("month", "6".to_string());
+        vars.insert("month_name", "Junio".to_string());
+        vars.insert("filename", "IMG_001.jpg".to_string());
+
+        assert_eq!(template.render(&vars), vec!["Imagenes", "2023", "06_Junio", "Anon", "IMG_001.jpg"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_templates() {
+        assert!(matches!(Template::parse("{media}/{yaer}/{filename}"), Err(TemplateError::UnknownVariable(..))));
+        assert!(matches!(Template::parse("{media}/{year}"), Err(TemplateError::NoFileName(_))));
+        assert!(matches!(Template::parse("{media}/{filename"), Err(TemplateError::Unclosed(_))));
+    }
+}